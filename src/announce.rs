@@ -0,0 +1,134 @@
+use anyhow::Result;
+use iroh::discovery::UserData;
+use std::str::FromStr;
+
+use crate::groups::SyncGroup;
+
+/// 服务声明前缀，用于在局域网发现中识别剪贴板同步节点
+const ANNOUNCE_PREFIX: &str = "clipboard-sync/1";
+
+/// 通过局域网发现广播的服务声明
+///
+/// 格式为 `clipboard-sync/1;g=<组标识,...>[;r=<会合编号>];n=<设备名>`。组标识由组密钥派生，
+/// 只有同组成员能识别，不会暴露组名。签发了配对码时附带会合编号。
+/// 设备名放在最后，允许包含分隔符。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceAnnouncement {
+    pub device_name: String,
+    pub group_tags: Vec<String>,
+    pub rendezvous: Option<String>,
+}
+
+impl ServiceAnnouncement {
+    /// 根据本机设备名和参与的组创建声明
+    pub fn new(device_name: &str, groups: &[SyncGroup]) -> Self {
+        Self {
+            device_name: device_name.to_string(),
+            group_tags: groups.iter().map(SyncGroup::discovery_tag).collect(),
+            rendezvous: None,
+        }
+    }
+
+    /// 附带配对码的会合编号
+    pub fn with_rendezvous(mut self, nameplate: &str) -> Self {
+        self.rendezvous = Some(nameplate.to_string());
+        self
+    }
+
+    /// 编码为发现服务的用户数据，设备名过长时会被截断
+    ///
+    /// 组过多导致声明头部本身超长时返回错误，避免对方把本机误认为未分组的节点。
+    pub fn to_user_data(&self) -> Result<UserData> {
+        let mut header = format!("{};g={}", ANNOUNCE_PREFIX, self.group_tags.join(","));
+        if let Some(nameplate) = &self.rendezvous {
+            header.push_str(&format!(";r={}", nameplate));
+        }
+        header.push_str(";n=");
+        if header.len() > UserData::MAX_LENGTH {
+            anyhow::bail!(
+                "同步组过多，局域网发现声明超出 {} 字节上限，请减少加入的组",
+                UserData::MAX_LENGTH
+            );
+        }
+        let mut encoded = header;
+        for ch in self.device_name.chars() {
+            if encoded.len() + ch.len_utf8() > UserData::MAX_LENGTH {
+                break;
+            }
+            encoded.push(ch);
+        }
+        UserData::from_str(&encoded).map_err(|e| anyhow::anyhow!("发现声明编码失败: {}", e))
+    }
+
+    /// 解析其他节点的用户数据，不是剪贴板同步节点时返回 None
+    pub fn parse(user_data: &str) -> Option<Self> {
+        let rest = user_data.strip_prefix(ANNOUNCE_PREFIX)?.strip_prefix(";g=")?;
+        let (tags, device_name) = rest.split_once(";n=")?;
+        let (tags, rendezvous) = match tags.split_once(";r=") {
+            Some((tags, nameplate)) => (tags, Some(nameplate.to_string())),
+            None => (tags, None),
+        };
+        Some(Self {
+            device_name: device_name.to_string(),
+            rendezvous,
+            group_tags: tags
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+
+    /// 判断对方是否与本机处于同一同步范围
+    ///
+    /// 本机未加入任何组时只匹配同样未分组的节点，否则至少需要一个共同的组。
+    pub fn matches(&self, groups: &[SyncGroup]) -> bool {
+        if groups.is_empty() {
+            return self.group_tags.is_empty();
+        }
+        groups
+            .iter()
+            .any(|group| self.group_tags.contains(&group.discovery_tag()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcement_round_trip_and_matching() {
+        let home = SyncGroup::generate("家里");
+        let work = SyncGroup::generate("公司");
+
+        let announcement = ServiceAnnouncement::new("书房;电脑", std::slice::from_ref(&home));
+        let user_data = announcement.to_user_data().expect("应能编码");
+        let parsed = ServiceAnnouncement::parse(user_data.as_ref()).expect("应能解析");
+
+        assert_eq!(parsed, announcement);
+        assert!(parsed.matches(&[home, work.clone()]));
+        assert!(!parsed.matches(&[work]));
+        assert!(!parsed.matches(&[]));
+
+        assert!(ServiceAnnouncement::parse("some-other-app").is_none());
+
+        let pairing = ServiceAnnouncement::new("笔记本", &[]).with_rendezvous("7");
+        let parsed = ServiceAnnouncement::parse(pairing.to_user_data().unwrap().as_ref()).expect("应能解析");
+        assert_eq!(parsed.rendezvous.as_deref(), Some("7"));
+        assert_eq!(parsed.device_name, "笔记本");
+    }
+
+    #[test]
+    fn test_oversized_announcement() {
+        // 设备名过长时截断
+        let long_name = "很长的设备名".repeat(100);
+        let user_data = ServiceAnnouncement::new(&long_name, &[]).to_user_data().expect("应能编码");
+        let parsed = ServiceAnnouncement::parse(user_data.as_ref()).expect("应能解析");
+        assert!(long_name.starts_with(&parsed.device_name));
+
+        // 组过多时报错，而不是退化成未分组的声明
+        let groups: Vec<SyncGroup> = (0..40).map(|i| SyncGroup::generate(&i.to_string())).collect();
+        assert!(ServiceAnnouncement::new("电脑", &groups).to_user_data().is_err());
+        assert!(ServiceAnnouncement::parse(ANNOUNCE_PREFIX).is_none());
+    }
+}
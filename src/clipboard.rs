@@ -1,8 +1,21 @@
 use anyhow::Result;
 use arboard::{Clipboard, ImageData};
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use image::{ImageFormat, RgbaImage};
 use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::concealed;
+use crate::headless::{FileClipboard, MemoryClipboard};
+use crate::network::ClipboardContent;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+use crate::wayland::WaylandClipboard;
+
+/// 等待剪贴板读写完成的最长时间，剪贴板所有者卡住时不再继续等待
+pub const ACCESS_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// 剪贴板内容类型
 #[derive(Debug, Clone, PartialEq)]
@@ -12,71 +25,287 @@ pub enum ClipboardContentType {
     Empty,
 }
 
+/// 内容所在的选区
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selection {
+    /// 复制粘贴使用的剪贴板
+    #[default]
+    Clipboard,
+    /// X11 和 Wayland 上选中即复制、中键粘贴的 PRIMARY 选区
+    Primary,
+}
+
+/// 剪贴板后端，负责实际读写某种剪贴板
+pub trait ClipboardBackend: Send + Sync {
+    /// 读取文字内容
+    fn get_text(&self) -> Result<String>;
+
+    /// 写入文字内容
+    fn set_text(&self, text: &str) -> Result<()>;
+
+    /// 读取图片，返回宽、高和 PNG 数据，没有图片时返回 None
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>>;
+
+    /// 写入 PNG 格式的图片
+    fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()>;
+
+    /// 清空剪贴板
+    fn clear(&self) -> Result<()>;
+
+    /// 当前内容是否被密码管理器标记为不应被记录
+    fn is_concealed(&self) -> bool {
+        false
+    }
+
+    /// 是否支持 PRIMARY 选区
+    fn supports_primary(&self) -> bool {
+        false
+    }
+
+    /// 读取 PRIMARY 选区的文字
+    fn get_primary(&self) -> Result<String> {
+        Err(anyhow::anyhow!("当前剪贴板不支持 PRIMARY 选区"))
+    }
+
+    /// 写入 PRIMARY 选区的文字
+    fn set_primary(&self, _text: &str) -> Result<()> {
+        Err(anyhow::anyhow!("当前剪贴板不支持 PRIMARY 选区"))
+    }
+
+    /// 清空 PRIMARY 选区
+    fn clear_primary(&self) -> Result<()> {
+        Err(anyhow::anyhow!("当前剪贴板不支持 PRIMARY 选区"))
+    }
+
+    /// 写入内容并保持提供，直到被其他程序替换才返回
+    ///
+    /// X11 和 Wayland 的剪贴板内容由写入的程序提供，程序退出后内容随之消失，
+    /// 写入后马上退出的命令需要用它代替 `set_text` / `set_image`。
+    fn set_and_wait(&self, content: &ClipboardContent) -> Result<()> {
+        match content {
+            ClipboardContent::Text(text) => self.set_text(text),
+            ClipboardContent::Image { width, height, data } => self.set_image(*width, *height, data),
+        }
+    }
+}
+
 /// 剪贴板管理器 - 负责读写剪贴板内容
 #[derive(Clone)]
 pub struct ClipboardManager {
-    clipboard: Arc<Mutex<Clipboard>>,
+    backend: Arc<dyn ClipboardBackend>,
+    applied: Arc<Mutex<Option<ClipboardContent>>>, // 本程序最近写入的内容，监控发现它时不再广播
+    applied_primary: Arc<Mutex<Option<String>>>, // 本程序最近写入 PRIMARY 选区的文字
 }
 
 impl ClipboardManager {
-    /// 创建新的剪贴板管理器
+    /// 创建使用系统剪贴板的管理器
+    ///
+    /// 设置了 `WAYLAND_DISPLAY` 时优先使用 Wayland data-control 协议，合成器不支持时改用 arboard。
     pub fn new() -> Result<Self> {
-        let clipboard = Clipboard::new()
-            .map_err(|e| anyhow::anyhow!("无法初始化剪贴板: {}", e))?;
-        
-        Ok(Self {
-            clipboard: Arc::new(Mutex::new(clipboard)),
-        })
+        #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match WaylandClipboard::new() {
+                Ok(backend) => {
+                    info!("通过 Wayland data-control 协议访问剪贴板");
+                    return Ok(Self::with_backend(backend));
+                }
+                Err(e) => warn!("{}，改用默认剪贴板", e),
+            }
+        }
+
+        Ok(Self::with_backend(SystemClipboard::new()?))
+    }
+
+    /// 创建不访问系统剪贴板的管理器，指定文件时内容保存在文件中，否则只保存在内存中
+    pub fn headless(file: Option<PathBuf>) -> Self {
+        match file {
+            Some(path) => Self::with_backend(FileClipboard::new(path)),
+            None => Self::with_backend(MemoryClipboard::default()),
+        }
+    }
+
+    pub fn with_backend(backend: impl ClipboardBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            applied: Arc::new(Mutex::new(None)),
+            applied_primary: Arc::new(Mutex::new(None)),
+        }
     }
 
     /// 获取剪贴板中的文字内容
     pub fn get_text(&self) -> Result<String> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        clipboard.get_text()
-            .map_err(|e| anyhow::anyhow!("读取剪贴板失败: {}", e))
+        self.backend.get_text()
     }
 
     /// 设置剪贴板文字内容
     pub fn set_text(&self, text: &str) -> Result<()> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        clipboard.set_text(text)
-            .map_err(|e| anyhow::anyhow!("写入剪贴板失败: {}", e))
+        self.write_applied(ClipboardContent::Text(text.to_string()), || self.backend.set_text(text))
     }
 
     /// 获取剪贴板中的图片内容
     pub fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        match clipboard.get_image() {
-            Ok(image_data) => {
-                // 将 RGBA 数据转换为 PNG 格式
-                let png_data = self.rgba_to_png(&image_data)?;
-                Ok(Some((image_data.width as u32, image_data.height as u32, png_data)))
-            }
-            Err(_) => Ok(None),
-        }
+        self.backend.get_image()
     }
     
     /// 设置剪贴板图片内容
     pub fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        
-        // 将 PNG 数据转换为 RGBA
-        let image_data = self.png_to_rgba(width, height, png_data)?;
-        clipboard.set_image(image_data)
-            .map_err(|e| anyhow::anyhow!("写入剪贴板图片失败: {}", e))
+        let content = ClipboardContent::Image {
+            width,
+            height,
+            data: png_data.to_vec(),
+        };
+        self.write_applied(content, || self.backend.set_image(width, height, png_data))
     }
     
+    /// 写入文字或图片
+    pub fn set_content(&self, content: &ClipboardContent) -> Result<()> {
+        match content {
+            ClipboardContent::Text(text) => self.set_text(text),
+            ClipboardContent::Image { width, height, data } => self.set_image(*width, *height, data),
+        }
+    }
+
+    /// 在阻塞线程中访问剪贴板，超过 `ACCESS_TIMEOUT` 仍未完成时返回错误
+    ///
+    /// X11、Wayland 上的读写需要与其他程序通信，对方卡住时不会拖住异步运行时。
+    pub async fn run_blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&ClipboardManager) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let clipboard = self.clone();
+        let task = tokio::task::spawn_blocking(move || f(&clipboard));
+        match tokio::time::timeout(ACCESS_TIMEOUT, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(anyhow::anyhow!("剪贴板任务异常退出: {}", e)),
+            Err(_) => Err(anyhow::anyhow!("访问剪贴板超时")),
+        }
+    }
+
+    /// 写入文字或图片，并阻塞到内容被其他程序替换，用于写入后就退出的命令
+    pub fn set_content_and_wait(&self, content: &ClipboardContent) -> Result<()> {
+        self.backend.set_and_wait(content)
+    }
+
+    /// 检查剪贴板中的内容是否是本程序最近写入的，是则消耗这条记录并返回 true
+    ///
+    /// 同步过来或从历史中写回的内容已经在其他设备上，监控发现它时据此跳过广播。
+    /// 内容不一致说明用户已经复制了别的内容，记录同样作废。
+    pub fn take_applied(&self, content: &ClipboardContent) -> bool {
+        let Some(applied) = self.applied.lock().unwrap().take() else {
+            return false;
+        };
+        match (&applied, content) {
+            (ClipboardContent::Text(applied), ClipboardContent::Text(current)) => applied == current,
+            // PNG 编码结果不唯一，按像素比较
+            (ClipboardContent::Image { data: applied, .. }, ClipboardContent::Image { data: current, .. }) => {
+                match (png_to_rgba(applied), png_to_rgba(current)) {
+                    (Ok(applied), Ok(current)) => applied == current,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// 写入前先记录，监控在写入完成的瞬间读到新内容时也能识别
+    fn write_applied(&self, content: ClipboardContent, write: impl FnOnce() -> Result<()>) -> Result<()> {
+        *self.applied.lock().unwrap() = Some(content);
+        let result = write();
+        if result.is_err() {
+            *self.applied.lock().unwrap() = None;
+        }
+        result
+    }
+
+    /// 当前内容仍是指定的内容时清空剪贴板，返回是否已清空
+    pub fn clear_if_matches(&self, content: &ClipboardContent) -> Result<bool> {
+        let unchanged = match content {
+            ClipboardContent::Text(text) => self.backend.get_text().is_ok_and(|current| &current == text),
+            ClipboardContent::Image { data, .. } => {
+                // PNG 编码结果不唯一，按像素比较
+                let expected = png_to_rgba(data)?;
+                match self.backend.get_image() {
+                    Ok(Some((_, _, current))) => png_to_rgba(&current).is_ok_and(|current| current == expected),
+                    _ => false,
+                }
+            }
+        };
+        if !unchanged {
+            return Ok(false);
+        }
+
+        self.backend.clear()?;
+        Ok(true)
+    }
+
+    /// PRIMARY 选区中仍是指定文字时清空选区，返回是否已清空
+    pub fn clear_primary_if_matches(&self, text: &str) -> Result<bool> {
+        if !self.backend.get_primary().is_ok_and(|current| current == text) {
+            return Ok(false);
+        }
+        self.backend.clear_primary()?;
+        Ok(true)
+    }
+
+    /// 在后台等待一段时间后清除选区中仍未被替换的内容
+    pub fn clear_after(&self, selection: Selection, content: ClipboardContent, delay: Duration) {
+        let clipboard = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let result = tokio::task::spawn_blocking(move || match (selection, &content) {
+                (Selection::Primary, ClipboardContent::Text(text)) => clipboard.clear_primary_if_matches(text),
+                (Selection::Primary, ClipboardContent::Image { .. }) => Ok(false),
+                (Selection::Clipboard, content) => clipboard.clear_if_matches(content),
+            })
+            .await;
+            match result {
+                Ok(Ok(true)) => info!("已清除到期的同步内容"),
+                Ok(Ok(false)) => debug!("剪贴板内容已变化，不再清除"),
+                Ok(Err(e)) => warn!("清除剪贴板失败: {}", e),
+                Err(e) => warn!("清除剪贴板失败: {}", e),
+            }
+        });
+    }
+
+    /// 当前内容是否被密码管理器标记为不应被记录
+    pub fn is_concealed(&self) -> bool {
+        self.backend.is_concealed()
+    }
+
+    pub fn supports_primary(&self) -> bool {
+        self.backend.supports_primary()
+    }
+
+    /// 获取 PRIMARY 选区中的文字
+    pub fn get_primary(&self) -> Result<String> {
+        self.backend.get_primary()
+    }
+
+    /// 设置 PRIMARY 选区的文字
+    pub fn set_primary(&self, text: &str) -> Result<()> {
+        *self.applied_primary.lock().unwrap() = Some(text.to_string());
+        let result = self.backend.set_primary(text);
+        if result.is_err() {
+            *self.applied_primary.lock().unwrap() = None;
+        }
+        result
+    }
+
+    /// 取出本程序最近写入 PRIMARY 选区的文字，监控据此避免把它再广播出去
+    pub fn take_applied_primary(&self) -> Option<String> {
+        self.applied_primary.lock().unwrap().take()
+    }
+
     /// 检测剪贴板内容类型
     pub fn get_content_type(&self) -> ClipboardContentType {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        
         // 先检查是否有图片
-        if clipboard.get_image().is_ok() {
+        if matches!(self.backend.get_image(), Ok(Some(_))) {
             return ClipboardContentType::Image;
         }
         
         // 再检查是否有文本
-        if let Ok(text) = clipboard.get_text() {
+        if let Ok(text) = self.backend.get_text() {
             if !text.is_empty() {
                 return ClipboardContentType::Text;
             }
@@ -84,46 +313,143 @@ impl ClipboardManager {
         
         ClipboardContentType::Empty
     }
-    
-    /// 检查剪贴板是否有内容
-    pub fn has_content(&self) -> bool {
-        !matches!(self.get_content_type(), ClipboardContentType::Empty)
+}
+
+/// 通过 arboard 访问的系统剪贴板
+pub struct SystemClipboard {
+    clipboard: Mutex<Clipboard>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        let clipboard = Clipboard::new()
+            .map_err(|e| anyhow::anyhow!("无法初始化剪贴板: {}", e))?;
+        Ok(Self {
+            clipboard: Mutex::new(clipboard),
+        })
     }
-    
-    /// 将 RGBA 数据转换为 PNG 格式
-    fn rgba_to_png(&self, image_data: &ImageData) -> Result<Vec<u8>> {
-        let rgba_image = RgbaImage::from_raw(
-            image_data.width as u32, 
-            image_data.height as u32, 
-            image_data.bytes.to_vec()
-        ).ok_or_else(|| anyhow::anyhow!("无法创建 RGBA 图像"))?;
-        
-        let mut png_data = Vec::new();
-        let mut cursor = Cursor::new(&mut png_data);
-        
-        rgba_image.write_to(&mut cursor, ImageFormat::Png)
-            .map_err(|e| anyhow::anyhow!("PNG 编码失败: {}", e))?;
-        
-        Ok(png_data)
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get_text(&self) -> Result<String> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.get_text()
+            .map_err(|e| anyhow::anyhow!("读取剪贴板失败: {}", e))
     }
-    
-    /// 将 PNG 数据转换为 RGBA 格式
-    fn png_to_rgba(&self, width: u32, height: u32, png_data: &[u8]) -> Result<ImageData> {
-        let cursor = Cursor::new(png_data);
-        let img = image::load(cursor, ImageFormat::Png)
-            .map_err(|e| anyhow::anyhow!("PNG 解码失败: {}", e))?;
-        
-        let rgba_img = img.to_rgba8();
-        let bytes = rgba_img.into_raw();
-        
-        Ok(ImageData {
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.set_text(text)
+            .map_err(|e| anyhow::anyhow!("写入剪贴板失败: {}", e))
+    }
+
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        match clipboard.get_image() {
+            Ok(image_data) => {
+                // 将 RGBA 数据转换为 PNG 格式
+                let png_data = rgba_to_png(&image_data)?;
+                Ok(Some((image_data.width as u32, image_data.height as u32, png_data)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+
+        // 将 PNG 数据转换为 RGBA
+        let image_data = ImageData {
             width: width as usize,
             height: height as usize,
-            bytes: bytes.into(),
-        })
+            bytes: png_to_rgba(png_data)?.into_raw().into(),
+        };
+        clipboard.set_image(image_data)
+            .map_err(|e| anyhow::anyhow!("写入剪贴板图片失败: {}", e))
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.clear()
+            .map_err(|e| anyhow::anyhow!("清空剪贴板失败: {}", e))
+    }
+
+    fn is_concealed(&self) -> bool {
+        concealed::clipboard_is_concealed()
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn supports_primary(&self) -> bool {
+        true
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn get_primary(&self) -> Result<String> {
+        use arboard::{GetExtLinux, LinuxClipboardKind};
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.get().clipboard(LinuxClipboardKind::Primary).text()
+            .map_err(|e| anyhow::anyhow!("读取 PRIMARY 选区失败: {}", e))
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn set_primary(&self, text: &str) -> Result<()> {
+        use arboard::{LinuxClipboardKind, SetExtLinux};
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.set().clipboard(LinuxClipboardKind::Primary).text(text)
+            .map_err(|e| anyhow::anyhow!("写入 PRIMARY 选区失败: {}", e))
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn clear_primary(&self) -> Result<()> {
+        use arboard::{ClearExtLinux, LinuxClipboardKind};
+        let mut clipboard = self.clipboard.lock().unwrap();
+        clipboard.clear_with().clipboard(LinuxClipboardKind::Primary)
+            .map_err(|e| anyhow::anyhow!("清空 PRIMARY 选区失败: {}", e))
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn set_and_wait(&self, content: &ClipboardContent) -> Result<()> {
+        use arboard::SetExtLinux;
+        let mut clipboard = self.clipboard.lock().unwrap();
+        match content {
+            ClipboardContent::Text(text) => clipboard.set().wait().text(text.as_str()),
+            ClipboardContent::Image { width, height, data } => {
+                let image_data = ImageData {
+                    width: *width as usize,
+                    height: *height as usize,
+                    bytes: png_to_rgba(data)?.into_raw().into(),
+                };
+                clipboard.set().wait().image(image_data)
+            }
+        }
+        .map_err(|e| anyhow::anyhow!("写入剪贴板失败: {}", e))
     }
 }
 
+/// 将 RGBA 数据转换为 PNG 格式
+fn rgba_to_png(image_data: &ImageData) -> Result<Vec<u8>> {
+    let rgba_image = RgbaImage::from_raw(
+        image_data.width as u32, 
+        image_data.height as u32, 
+        image_data.bytes.to_vec()
+    ).ok_or_else(|| anyhow::anyhow!("无法创建 RGBA 图像"))?;
+    
+    let mut png_data = Vec::new();
+    let mut cursor = Cursor::new(&mut png_data);
+    
+    rgba_image.write_to(&mut cursor, ImageFormat::Png)
+        .map_err(|e| anyhow::anyhow!("PNG 编码失败: {}", e))?;
+    
+    Ok(png_data)
+}
+
+/// 将 PNG 数据转换为 RGBA 格式
+fn png_to_rgba(png_data: &[u8]) -> Result<RgbaImage> {
+    let cursor = Cursor::new(png_data);
+    let img = image::load(cursor, ImageFormat::Png)
+        .map_err(|e| anyhow::anyhow!("PNG 解码失败: {}", e))?;
+    Ok(img.to_rgba8())
+}
 
 #[cfg(test)]
 mod tests {
@@ -140,4 +466,33 @@ mod tests {
         let result = manager.get_text().expect("读取失败");
         assert_eq!(result, test_text);
     }
+
+    #[test]
+    fn test_applied_content_is_consumed_once() {
+        let manager = ClipboardManager::headless(None);
+        let text = |s: &str| ClipboardContent::Text(s.to_string());
+
+        manager.set_text("同步过来的内容").unwrap();
+        assert!(manager.take_applied(&text("同步过来的内容")));
+        assert!(!manager.take_applied(&text("同步过来的内容")));
+
+        // 用户复制了别的内容后，记录作废
+        manager.set_text("同步过来的内容").unwrap();
+        assert!(!manager.take_applied(&text("用户复制的内容")));
+        assert!(!manager.take_applied(&text("同步过来的内容")));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_access_times_out() {
+        let manager = ClipboardManager::headless(None);
+        manager.run_blocking(|clipboard| clipboard.set_text("你好")).await.unwrap();
+        assert_eq!(manager.get_text().unwrap(), "你好");
+
+        // 模拟卡住的剪贴板所有者
+        let stalled = manager.run_blocking(|_| {
+            std::thread::sleep(ACCESS_TIMEOUT * 2);
+            Ok(())
+        });
+        assert!(stalled.await.is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,186 @@
+/// 密码管理器用来标记剪贴板内容“不应被剪贴板工具记录”的类型
+const CONCEALED_HINTS: &[&str] = &[
+    // KDE Klipper 约定，KeePassXC 等在 X11 和 Wayland 上使用
+    "x-kde-passwordManagerHint",
+    // Windows 剪贴板格式，1Password、KeePass、Bitwarden 等使用
+    "ExcludeClipboardContentFromMonitorProcessing",
+    // macOS nspasteboard.org 约定
+    "org.nspasteboard.ConcealedType",
+];
+
+/// 检查当前剪贴板内容是否带有密码管理器的隐藏标记
+///
+/// 只在剪贴板后端能列出内容类型的平台上生效，其他平台总是返回 false。
+pub fn clipboard_is_concealed() -> bool {
+    platform::has_concealed_hint()
+}
+
+/// 剪贴板内容类型是否为密码管理器的隐藏标记
+pub fn is_concealed_type(name: &str) -> bool {
+    CONCEALED_HINTS.contains(&name)
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+mod platform {
+    use super::CONCEALED_HINTS;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, Window, WindowClass};
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    /// 等待剪贴板所有者回复 TARGETS 的最长时间
+    const TARGETS_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// 复用的 X11 连接，出错或超时后丢弃，下次重新建立
+    static READER: Mutex<Option<TargetsReader>> = Mutex::new(None);
+
+    pub fn has_concealed_hint() -> bool {
+        let mut reader = READER.lock().unwrap();
+        if reader.is_none() {
+            *reader = TargetsReader::connect();
+        }
+        let Some(current) = reader.as_ref() else {
+            return false;
+        };
+
+        match current.clipboard_targets() {
+            Ok(targets) => targets
+                .iter()
+                .any(|target| CONCEALED_HINTS.contains(&target.as_str())),
+            Err(()) => {
+                // 超时后对方可能稍后才回复，继续使用这条连接会读到过期的事件
+                *reader = None;
+                false
+            }
+        }
+    }
+
+    /// 用于读取剪贴板类型列表的 X11 连接和隐藏窗口
+    struct TargetsReader {
+        conn: RustConnection,
+        window: Window,
+        clipboard: u32,
+        targets: u32,
+        property: u32,
+    }
+
+    impl TargetsReader {
+        fn connect() -> Option<Self> {
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let screen = &conn.setup().roots[screen_num];
+
+            let window = conn.generate_id().ok()?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                screen.root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new(),
+            )
+            .ok()?;
+
+            let intern = |name: &[u8]| -> Option<u32> {
+                Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+            };
+            let clipboard = intern(b"CLIPBOARD")?;
+            let targets = intern(b"TARGETS")?;
+            let property = intern(b"CLIPBOARD_SYNC_TARGETS")?;
+
+            Some(Self {
+                conn,
+                window,
+                clipboard,
+                targets,
+                property,
+            })
+        }
+
+        /// 通过 X11 选区协议读取 CLIPBOARD 提供的全部 MIME 类型
+        ///
+        /// 没有所有者或所有者拒绝转换时返回空列表，连接出错或超时返回 Err。
+        fn clipboard_targets(&self) -> Result<Vec<String>, ()> {
+            let conn = &self.conn;
+            conn.convert_selection(self.window, self.clipboard, self.targets, self.property, x11rb::CURRENT_TIME)
+                .map_err(drop)?;
+            conn.flush().map_err(drop)?;
+
+            let deadline = Instant::now() + TARGETS_TIMEOUT;
+            loop {
+                match conn.poll_for_event().map_err(drop)? {
+                    Some(Event::SelectionNotify(event)) if event.requestor == self.window => {
+                        if event.property == x11rb::NONE {
+                            return Ok(Vec::new());
+                        }
+                        break;
+                    }
+                    Some(_) => continue,
+                    None if Instant::now() >= deadline => return Err(()),
+                    None => std::thread::sleep(Duration::from_millis(5)),
+                }
+            }
+
+            let reply = conn
+                .get_property(true, self.window, self.property, AtomEnum::ATOM, 0, 1024)
+                .map_err(drop)?
+                .reply()
+                .map_err(drop)?;
+            let names = reply
+                .value32()
+                .into_iter()
+                .flatten()
+                .filter_map(|atom| conn.get_atom_name(atom).ok()?.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                .collect();
+            Ok(names)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::CONCEALED_HINTS;
+
+    pub fn has_concealed_hint() -> bool {
+        CONCEALED_HINTS.iter().any(|name| {
+            clipboard_win::register_format(name)
+                .is_some_and(|format| clipboard_win::is_format_avail(format.get()))
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::CONCEALED_HINTS;
+    use objc2_app_kit::NSPasteboard;
+
+    pub fn has_concealed_hint() -> bool {
+        // SAFETY: 只读取通用剪贴板的类型列表，不修改剪贴板
+        let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+        let Some(types) = (unsafe { pasteboard.types() }) else {
+            return false;
+        };
+        types
+            .to_vec()
+            .iter()
+            .any(|pasteboard_type| CONCEALED_HINTS.contains(&pasteboard_type.to_string().as_str()))
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "macos",
+    all(unix, not(any(target_os = "android", target_os = "emscripten")))
+)))]
+mod platform {
+    pub fn has_concealed_hint() -> bool {
+        false
+    }
+}
@@ -0,0 +1,492 @@
+use anyhow::Result;
+use iroh::NodeId;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::network::{ClipboardContent, SyncRules, TransportMode, DEFAULT_MAX_HOPS};
+use crate::primary::PrimaryFallback;
+use crate::sensitive::SensitivePolicy;
+use crate::storage;
+use crate::trust::ContentKind;
+
+/// 配置文件名，位于配置目录下
+const CONFIG_FILE: &str = "config.toml";
+
+/// 未配置设备名称时使用的名称
+const DEFAULT_DEVICE_NAME: &str = "我的设备";
+
+/// 剪贴板轮询间隔的下限，过短会占用大量 CPU
+const MIN_POLL_INTERVAL_MS: u64 = 50;
+
+/// 配置文件
+///
+/// 顶层是所有 profile 共用的设置，`[profiles.<名称>]` 中的同名设置会覆盖它们：
+///
+/// ```toml
+/// device_name = "笔记本"
+/// default_profile = "home"
+///
+/// [transport]
+/// relay = true
+///
+/// [profiles.work]
+/// groups = ["team"]
+/// transport = { local_discovery = false }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 设备名称
+    pub device_name: Option<String>,
+    /// 参与同步的命名组
+    pub groups: Vec<String>,
+    /// 无需批准即可同步的设备节点 ID
+    pub trusted_peers: Vec<NodeId>,
+    /// 剪贴板轮询间隔（毫秒）
+    pub poll_interval_ms: u64,
+    /// 同步的内容在其他设备上保留的秒数
+    pub clear_after: Option<u64>,
+    pub limits: SizeLimits,
+    pub filters: ContentFilter,
+    pub sensitive: SensitiveSettings,
+    pub notifications: NotificationSettings,
+    pub primary: PrimarySettings,
+    pub transport: TransportSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            device_name: None,
+            groups: Vec::new(),
+            trusted_peers: Vec::new(),
+            poll_interval_ms: 500,
+            clear_after: None,
+            limits: SizeLimits::default(),
+            filters: ContentFilter::default(),
+            sensitive: SensitiveSettings::default(),
+            notifications: NotificationSettings::default(),
+            primary: PrimarySettings::default(),
+            transport: TransportSettings::default(),
+        }
+    }
+}
+
+/// 同步内容的大小上限，超过的内容既不发送也不应用
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeLimits {
+    /// 文本的最大字节数
+    pub max_text_bytes: Option<usize>,
+    /// 图片（PNG 编码后）的最大字节数
+    pub max_image_bytes: Option<usize>,
+}
+
+impl SizeLimits {
+    pub fn allows(&self, content: &ClipboardContent) -> bool {
+        let (size, limit) = match content {
+            ClipboardContent::Text(text) => (text.len(), self.max_text_bytes),
+            ClipboardContent::Image { data, .. } => (data.len(), self.max_image_bytes),
+        };
+        limit.is_none_or(|limit| size <= limit)
+    }
+}
+
+/// 本机内容的同步过滤规则
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentFilter {
+    /// 允许同步的内容类型
+    pub content: Vec<ContentKind>,
+    /// 包含任一字符串的文本不同步
+    pub ignore: Vec<String>,
+}
+
+impl Default for ContentFilter {
+    fn default() -> Self {
+        Self {
+            content: ContentKind::ALL.to_vec(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+impl ContentFilter {
+    pub fn allows(&self, content: &ClipboardContent) -> bool {
+        if !self.content.contains(&ContentKind::of(content)) {
+            return false;
+        }
+        match content {
+            ClipboardContent::Text(text) => !self.ignore.iter().any(|pattern| text.contains(pattern.as_str())),
+            ClipboardContent::Image { .. } => true,
+        }
+    }
+}
+
+/// 疑似敏感内容的处理方式
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensitiveSettings {
+    pub policy: SensitivePolicy,
+    /// 同步后在其他设备上保留的秒数，0 表示不自动清除
+    pub clear_after: u64,
+}
+
+impl Default for SensitiveSettings {
+    fn default() -> Self {
+        Self {
+            policy: SensitivePolicy::Skip,
+            clear_after: 60,
+        }
+    }
+}
+
+/// 系统通知设置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    /// 通知显示的毫秒数
+    pub timeout_ms: u32,
+    /// 通知中文本预览的最大字符数
+    pub preview_length: usize,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: 3000,
+            preview_length: 50,
+        }
+    }
+}
+
+/// PRIMARY 选区（选中即复制、中键粘贴）的同步设置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrimarySettings {
+    /// 广播本机 PRIMARY 选区的变化
+    pub send: bool,
+    /// 应用其他设备发来的 PRIMARY 选区内容
+    pub receive: bool,
+    /// 选区内容保持不变多少毫秒后才广播
+    pub debounce_ms: u64,
+    /// 本机不支持 PRIMARY 选区时如何处理收到的内容
+    pub fallback: PrimaryFallback,
+}
+
+impl Default for PrimarySettings {
+    fn default() -> Self {
+        Self {
+            send: false,
+            receive: false,
+            debounce_ms: 750,
+            fallback: PrimaryFallback::Ignore,
+        }
+    }
+}
+
+impl PrimarySettings {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+}
+
+/// 传输设置
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransportSettings {
+    pub mode: TransportMode,
+    /// 是否转发收到的消息
+    pub relay: bool,
+    pub max_hops: u8,
+    /// gossip 模式下共享的组密钥
+    pub group_secret: Option<String>,
+    /// 是否启用局域网设备发现
+    pub local_discovery: bool,
+}
+
+impl Default for TransportSettings {
+    fn default() -> Self {
+        Self {
+            mode: TransportMode::Direct,
+            relay: false,
+            max_hops: DEFAULT_MAX_HOPS,
+            group_secret: None,
+            local_discovery: true,
+        }
+    }
+}
+
+impl Config {
+    /// 默认配置文件路径
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(storage::config_dir()?.join(CONFIG_FILE))
+    }
+
+    /// 加载配置文件并应用 profile，默认路径下没有配置文件时使用默认配置
+    ///
+    /// 未指定 profile 时使用文件中的 `default_profile`。
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = Self::default_path()?;
+                if !path.exists() {
+                    if let Some(profile) = profile {
+                        anyhow::bail!("配置文件 {} 不存在，无法使用 profile: {}", path.display(), profile);
+                    }
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", path.display(), e))?;
+        Self::parse(&text, profile).map_err(|e| anyhow::anyhow!("配置文件 {} 无效: {}", path.display(), e))
+    }
+
+    /// 解析配置内容并应用 profile
+    pub fn parse(text: &str, profile: Option<&str>) -> Result<Self> {
+        let mut root: toml::Table = text.parse().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        let default_profile = match root.remove("default_profile") {
+            Some(toml::Value::String(name)) => Some(name),
+            Some(_) => anyhow::bail!("default_profile 必须是字符串"),
+            None => None,
+        };
+        let mut profiles = match root.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => anyhow::bail!("profiles 必须是表"),
+            None => toml::Table::new(),
+        };
+
+        if let Some(name) = profile.map(str::to_string).or(default_profile) {
+            let overrides = match profiles.remove(&name) {
+                Some(toml::Value::Table(overrides)) => overrides,
+                Some(_) => anyhow::bail!("profile {} 必须是表", name),
+                None => {
+                    let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                    anyhow::bail!("没有名为 {} 的 profile（可用: {}）", name, available.join(", "));
+                }
+            };
+            merge_tables(&mut root, overrides);
+        }
+
+        let config: Self = toml::Value::Table(root)
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.poll_interval_ms < MIN_POLL_INTERVAL_MS {
+            anyhow::bail!("poll_interval_ms 不能小于 {}", MIN_POLL_INTERVAL_MS);
+        }
+        if self.device_name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            anyhow::bail!("device_name 不能为空");
+        }
+        // 通知接口使用 i32 表示超时时间
+        if i32::try_from(self.notifications.timeout_ms).is_err() {
+            anyhow::bail!("notifications.timeout_ms 不能大于 {}", i32::MAX);
+        }
+        Ok(())
+    }
+
+    pub fn device_name(&self) -> &str {
+        self.device_name.as_deref().unwrap_or(DEFAULT_DEVICE_NAME)
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    /// 运行中可以直接替换的同步规则
+    pub fn sync_rules(&self) -> SyncRules {
+        SyncRules {
+            trusted_peers: self.trusted_peers.clone(),
+            limits: self.limits.clone(),
+            filters: self.filters.clone(),
+            sensitive_policy: self.sensitive.policy,
+            clear_after: self.clear_after.map(Duration::from_secs),
+            sensitive_clear_after: (self.sensitive.clear_after > 0)
+                .then(|| Duration::from_secs(self.sensitive.clear_after)),
+        }
+    }
+
+    /// 与新配置相比，需要重启服务才能生效的设置
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.device_name != new.device_name {
+            changed.push("device_name");
+        }
+        if self.groups != new.groups {
+            changed.push("groups");
+        }
+        if self.transport != new.transport {
+            changed.push("transport");
+        }
+        changed
+    }
+}
+
+/// 命令行中指定的设置，优先于配置文件
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub device_name: Option<String>,
+    pub groups: Vec<String>,
+    pub clear_after: Option<u64>,
+    pub sensitive_policy: Option<SensitivePolicy>,
+    pub sensitive_clear_after: Option<u64>,
+    pub relay: Option<bool>,
+    pub max_hops: Option<u8>,
+    pub transport: Option<TransportMode>,
+    pub group_secret: Option<String>,
+    pub headless: bool,
+    pub primary_send: Option<bool>,
+    pub primary_receive: Option<bool>,
+}
+
+impl ConfigOverrides {
+    fn apply(&self, config: &mut Config) {
+        if let Some(name) = &self.device_name {
+            config.device_name = Some(name.clone());
+        }
+        if !self.groups.is_empty() {
+            config.groups = self.groups.clone();
+        }
+        if let Some(clear_after) = self.clear_after {
+            config.clear_after = Some(clear_after);
+        }
+        if let Some(policy) = self.sensitive_policy {
+            config.sensitive.policy = policy;
+        }
+        if let Some(clear_after) = self.sensitive_clear_after {
+            config.sensitive.clear_after = clear_after;
+        }
+        if let Some(relay) = self.relay {
+            config.transport.relay = relay;
+        }
+        if let Some(max_hops) = self.max_hops {
+            config.transport.max_hops = max_hops;
+        }
+        if let Some(mode) = self.transport {
+            config.transport.mode = mode;
+        }
+        if let Some(secret) = &self.group_secret {
+            config.transport.group_secret = Some(secret.clone());
+        }
+        if let Some(send) = self.primary_send {
+            config.primary.send = send;
+        }
+        if let Some(receive) = self.primary_receive {
+            config.primary.receive = receive;
+        }
+        // 无界面模式下没有桌面通知服务
+        if self.headless {
+            config.notifications.enabled = false;
+        }
+    }
+}
+
+/// 配置的来源，重新加载时按同样的文件、profile 和命令行参数得到新配置
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub overrides: ConfigOverrides,
+}
+
+impl ConfigSource {
+    pub fn load(&self) -> Result<Config> {
+        let mut config = Config::load(self.path.as_deref(), self.profile.as_deref())?;
+        self.overrides.apply(&mut config);
+        Ok(config)
+    }
+
+    /// 实际使用的配置文件路径
+    pub fn path(&self) -> Result<PathBuf> {
+        match &self.path {
+            Some(path) => Ok(path.clone()),
+            None => Config::default_path(),
+        }
+    }
+}
+
+/// 将 profile 中的设置合并到顶层设置，嵌套的表逐项覆盖
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => merge_tables(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+        device_name = "笔记本"
+        default_profile = "home"
+
+        [notifications]
+        timeout_ms = 5000
+
+        [profiles.home]
+        groups = ["family"]
+        trusted_peers = ["8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"]
+
+        [profiles.work]
+        groups = ["team"]
+        filters = { content = ["text"], ignore = ["内部"] }
+        notifications = { enabled = false }
+        transport = { local_discovery = false }
+    "#;
+
+    #[test]
+    fn test_profiles_override_shared_settings() {
+        let home = Config::parse(EXAMPLE, None).unwrap();
+        assert_eq!(home.device_name.as_deref(), Some("笔记本"));
+        assert_eq!(home.groups, vec!["family"]);
+        assert!(home.transport.local_discovery);
+        assert_eq!(home.trusted_peers.len(), 1);
+
+        let work = Config::parse(EXAMPLE, Some("work")).unwrap();
+        assert_eq!(work.groups, vec!["team"]);
+        assert!(!work.notifications.enabled);
+        assert_eq!(work.notifications.timeout_ms, 5000);
+        assert!(!work.transport.local_discovery);
+        assert!(!work.filters.allows(&ClipboardContent::Text("内部资料".to_string())));
+        assert!(work.filters.allows(&ClipboardContent::Text("公开资料".to_string())));
+        assert_eq!(home.restart_required(&work), vec!["groups", "transport"]);
+
+        assert!(Config::parse(EXAMPLE, Some("travel")).is_err());
+        assert!(Config::parse("poll_interval_ms = 10", None).is_err());
+        assert!(Config::parse("unknown_key = 1", None).is_err());
+        assert!(Config::parse("[notifications]\ntimeout_ms = 3000000000", None).is_err());
+    }
+
+    #[test]
+    fn test_overrides_can_turn_settings_off() {
+        let mut config = Config::parse("[transport]\nrelay = true", None).unwrap();
+        ConfigOverrides::default().apply(&mut config);
+        assert!(config.transport.relay);
+
+        let overrides = ConfigOverrides {
+            relay: Some(false),
+            primary_send: Some(false),
+            ..Default::default()
+        };
+        config.primary.send = true;
+        overrides.apply(&mut config);
+        assert!(!config.transport.relay);
+        assert!(!config.primary.send);
+    }
+}
@@ -0,0 +1,480 @@
+use anyhow::Result;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
+
+use crate::clipboard::{ClipboardManager, Selection};
+use crate::history::{HistoryEntry, HistorySource};
+use crate::network::{ClipboardContent, NetworkManager};
+use crate::pairing;
+use crate::pause::PauseDirection;
+use crate::reload::ConfigReloader;
+use crate::storage;
+use crate::trust::PeerPolicy;
+
+/// 控制通道文件名，记录运行中服务的端口和令牌
+const CONTROL_FILE: &str = "control.json";
+
+/// 运行中服务的控制通道信息
+#[derive(Debug, Serialize, Deserialize)]
+struct ControlEndpoint {
+    port: u16,
+    token: String,
+    pid: u32,
+}
+
+/// 控制命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// 列出发现的设备和可信设备
+    ListDevices,
+    /// 批准设备，目标可以是发现列表中的编号或节点 ID
+    ApproveDevice { target: String },
+    /// 撤销对设备的信任
+    RevokeDevice { node_id: String },
+    /// 设置可信设备的同步方向和允许的内容类型
+    SetPeerPolicy { node_id: String, policy: PeerPolicy },
+    /// 由运行中的服务签发连接票据
+    IssueTicket { ttl_secs: u64, single_use: bool },
+    /// 由运行中的服务签发配对码
+    IssuePairingCode { ttl_secs: u64 },
+    /// 同步或放弃等待确认的敏感内容
+    ResolveSensitive { allow: bool },
+    /// 暂停同步，minutes 为空时直到手动恢复
+    Pause {
+        direction: PauseDirection,
+        minutes: Option<u64>,
+    },
+    /// 恢复同步
+    Resume { direction: PauseDirection },
+    /// 开启或关闭隐身模式
+    SetIncognito { enabled: bool },
+    /// 查询暂停和隐身模式的状态
+    Status,
+    /// 重新加载配置文件
+    ReloadConfig,
+    /// 将内容发送给已连接的设备，peers 为空时发给全部设备
+    SendContent {
+        content: ClipboardContent,
+        peers: Vec<NodeId>,
+    },
+    /// 取最新同步的内容，wait 为 true 时等待其他设备发来的下一条
+    Receive { wait: bool, timeout_secs: Option<u64> },
+    /// 列出最近同步过的内容
+    ListHistory,
+    /// 固定或取消固定历史条目
+    PinHistory { hash: String, pinned: bool },
+    /// 删除历史条目
+    DeleteHistory { hash: String },
+    /// 将历史条目写回本机剪贴板，broadcast 为 true 时同时发给其他设备
+    RestoreHistory { hash: String, broadcast: bool },
+    /// 新建或修改片段
+    SetSnippet { name: String, content: ClipboardContent },
+    /// 删除片段
+    RemoveSnippet { name: String },
+    /// 将片段内容写入本机剪贴板
+    CopySnippet { name: String },
+}
+
+/// 控制命令的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    pub message: String,
+    #[serde(default)]
+    pub content: Option<ClipboardContent>, // Receive 命令取到的内容
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>, // ListHistory 命令取到的条目
+}
+
+impl ControlResponse {
+    fn ok(message: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            message: message.into(),
+            content: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            message: message.into(),
+            content: None,
+            history: Vec::new(),
+        }
+    }
+}
+
+/// 带令牌的控制请求
+#[derive(Debug, Serialize, Deserialize)]
+struct ControlEnvelope {
+    token: String,
+    request: ControlRequest,
+}
+
+/// 启动本地控制通道，供命令行向运行中的服务发送命令
+///
+/// 只监听回环地址，并要求请求携带保存在配置目录中的随机令牌。
+pub async fn serve(network: NetworkManager, clipboard: ClipboardManager, reloader: ConfigReloader) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let endpoint = ControlEndpoint {
+        port: listener.local_addr()?.port(),
+        token: hex::encode(rand::random::<[u8; 16]>()),
+        pid: std::process::id(),
+    };
+    write_endpoint(&endpoint)?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let network = network.clone();
+        let clipboard = clipboard.clone();
+        let reloader = reloader.clone();
+        let token = endpoint.token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, &token, &network, &clipboard, &reloader).await {
+                warn!("控制命令处理失败: {}", e);
+            }
+        });
+    }
+}
+
+/// 向运行中的服务发送控制命令，服务未运行时返回 None
+pub async fn send_request(request: ControlRequest) -> Result<Option<ControlResponse>> {
+    let path = storage::config_dir()?.join(CONTROL_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let endpoint: ControlEndpoint = serde_json::from_slice(&std::fs::read(&path)?)?;
+
+    // 连接失败说明服务已经退出，控制文件是残留的
+    let Ok(stream) = TcpStream::connect(("127.0.0.1", endpoint.port)).await else {
+        return Ok(None);
+    };
+
+    let (reader, mut writer) = stream.into_split();
+    let envelope = ControlEnvelope {
+        token: endpoint.token,
+        request,
+    };
+    let mut line = serde_json::to_string(&envelope)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    Ok(Some(serde_json::from_str(&response)?))
+}
+
+/// 在终端中读取命令（如 `approve 1`），与控制通道执行相同的操作
+pub async fn run_prompt(network: NetworkManager, clipboard: ClipboardManager, reloader: ConfigReloader) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut parts = line.split_whitespace();
+        let request = match (parts.next(), parts.next()) {
+            (None, _) => continue,
+            (Some("list"), _) => ControlRequest::ListDevices,
+            (Some("code"), _) => ControlRequest::IssuePairingCode {
+                ttl_secs: pairing::DEFAULT_CODE_TTL.as_secs(),
+            },
+            (Some("allow"), _) => ControlRequest::ResolveSensitive { allow: true },
+            (Some("deny"), _) => ControlRequest::ResolveSensitive { allow: false },
+            (Some("pause"), None) => ControlRequest::Pause {
+                direction: PauseDirection::Both,
+                minutes: None,
+            },
+            (Some("pause"), Some(minutes)) if minutes.parse::<u64>().is_ok() => ControlRequest::Pause {
+                direction: PauseDirection::Both,
+                minutes: minutes.parse().ok(),
+            },
+            (Some("resume"), _) => ControlRequest::Resume {
+                direction: PauseDirection::Both,
+            },
+            (Some("incognito"), Some("on")) => ControlRequest::SetIncognito { enabled: true },
+            (Some("incognito"), Some("off")) => ControlRequest::SetIncognito { enabled: false },
+            (Some("status"), _) => ControlRequest::Status,
+            (Some("reload"), _) => ControlRequest::ReloadConfig,
+            (Some("approve"), Some(target)) => ControlRequest::ApproveDevice {
+                target: target.to_string(),
+            },
+            (Some("revoke"), Some(node_id)) => ControlRequest::RevokeDevice {
+                node_id: node_id.to_string(),
+            },
+            _ => {
+                println!(
+                    "可用命令: list | code | allow | deny | pause [分钟] | resume | incognito on|off | status | reload | approve <编号或节点ID> | revoke <节点ID>"
+                );
+                continue;
+            }
+        };
+
+        let response = handle_request(&network, &clipboard, &reloader, request).await;
+        if response.ok {
+            println!("{}", response.message);
+        } else {
+            eprintln!("{}", response.message);
+        }
+    }
+}
+
+fn write_endpoint(endpoint: &ControlEndpoint) -> Result<()> {
+    let path = storage::config_dir()?.join(CONTROL_FILE);
+
+    // 令牌只允许当前用户读取，删除旧文件后重新创建，保证创建时就是私有权限
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(anyhow::anyhow!("删除旧的控制端点文件失败: {}", e)),
+    }
+    let mut file = storage::create_private(&path)
+        .map_err(|e| anyhow::anyhow!("创建控制端点文件失败: {}", e))?;
+    file.write_all(&serde_json::to_vec(endpoint)?)?;
+
+    Ok(())
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    token: &str,
+    network: &NetworkManager,
+    clipboard: &ClipboardManager,
+    reloader: &ConfigReloader,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let response = match serde_json::from_str::<ControlEnvelope>(&line) {
+        Ok(envelope) if envelope.token == token => {
+            handle_request(network, clipboard, reloader, envelope.request).await
+        }
+        Ok(_) => ControlResponse::error("控制令牌无效"),
+        Err(e) => ControlResponse::error(format!("无法解析控制命令: {}", e)),
+    };
+
+    let mut data = serde_json::to_string(&response)?;
+    data.push('\n');
+    writer.write_all(data.as_bytes()).await?;
+    Ok(())
+}
+
+async fn handle_request(
+    network: &NetworkManager,
+    clipboard: &ClipboardManager,
+    reloader: &ConfigReloader,
+    request: ControlRequest,
+) -> ControlResponse {
+    match request {
+        ControlRequest::ListDevices => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+
+            let mut lines = vec!["待批准的设备:".to_string()];
+            let discovered = network.discovered_devices().await;
+            if discovered.is_empty() {
+                lines.push("  (无)".to_string());
+            }
+            for device in &discovered {
+                lines.push(format!(
+                    "  [{}] {} ({}) 首次发现于 {} 前",
+                    device.number,
+                    device.device_name,
+                    device.node_id,
+                    format_elapsed(now.saturating_sub(device.first_seen)),
+                ));
+            }
+
+            lines.push("可信设备:".to_string());
+            let trusted = network.trusted_devices().await;
+            if trusted.is_empty() {
+                lines.push("  (无)".to_string());
+            }
+            for device in trusted {
+                lines.push(format!(
+                    "  {} ({}) [{}]",
+                    device.device_name,
+                    device.node_id,
+                    device.policy.describe()
+                ));
+            }
+
+            ControlResponse::ok(lines.join("\n"))
+        }
+        ControlRequest::ApproveDevice { target } => match network.approve_device(&target).await {
+            Ok(device_name) => ControlResponse::ok(format!("已批准设备: {}", device_name)),
+            Err(e) => ControlResponse::error(format!("批准设备失败: {}", e)),
+        },
+        ControlRequest::RevokeDevice { node_id } => match network.revoke_device(&node_id).await {
+            Ok(()) => ControlResponse::ok(format!("已撤销对设备的信任: {}", node_id)),
+            Err(e) => ControlResponse::error(format!("撤销失败: {}", e)),
+        },
+        ControlRequest::SetPeerPolicy { node_id, policy } => {
+            let description = policy.describe();
+            match network.set_peer_policy(&node_id, policy).await {
+                Ok(device_name) => ControlResponse::ok(format!("已更新 {} 的同步策略: {}", device_name, description)),
+                Err(e) => ControlResponse::error(format!("设置同步策略失败: {}", e)),
+            }
+        }
+        ControlRequest::IssueTicket { ttl_secs, single_use } => {
+            match network
+                .generate_ticket(std::time::Duration::from_secs(ttl_secs), single_use)
+                .await
+            {
+                Ok(ticket) => ControlResponse::ok(ticket),
+                Err(e) => ControlResponse::error(format!("生成票据失败: {}", e)),
+            }
+        }
+        ControlRequest::IssuePairingCode { ttl_secs } => {
+            match network
+                .issue_pairing_code(std::time::Duration::from_secs(ttl_secs))
+                .await
+            {
+                Ok(code) => ControlResponse::ok(code),
+                Err(e) => ControlResponse::error(format!("生成配对码失败: {}", e)),
+            }
+        }
+        ControlRequest::ResolveSensitive { allow } => match network.resolve_sensitive(allow).await {
+            Ok(false) => ControlResponse::error("没有等待确认的敏感内容"),
+            Ok(true) if allow => ControlResponse::ok("已同步敏感内容"),
+            Ok(true) => ControlResponse::ok("已放弃同步敏感内容"),
+            Err(e) => ControlResponse::error(format!("同步失败: {}", e)),
+        },
+        ControlRequest::Pause { direction, minutes } => {
+            let duration = minutes.map(|minutes| std::time::Duration::from_secs(minutes * 60));
+            network.pause_sync(direction, duration).await;
+            ControlResponse::ok(network.sync_status().await)
+        }
+        ControlRequest::Resume { direction } => {
+            network.resume_sync(direction).await;
+            ControlResponse::ok(network.sync_status().await)
+        }
+        ControlRequest::SetIncognito { enabled } => {
+            network.set_incognito(enabled).await;
+            ControlResponse::ok(network.sync_status().await)
+        }
+        ControlRequest::Status => ControlResponse::ok(network.sync_status().await),
+        ControlRequest::SendContent { content, peers } => match network.send_content(content, &peers, None).await {
+            Ok(delivered) => ControlResponse::ok(describe_delivery(delivered)),
+            Err(e) => ControlResponse::error(format!("发送失败: {}", e)),
+        },
+        ControlRequest::Receive { wait, timeout_secs } => {
+            let history = network.history();
+            let entry = if wait {
+                // 只等待其他设备发来的内容
+                let mut updates = history.subscribe();
+                let next = async {
+                    loop {
+                        match updates.recv().await {
+                            Ok(entry) if matches!(entry.source, HistorySource::Remote(_)) => return Some(entry),
+                            Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                        }
+                    }
+                };
+                match timeout_secs {
+                    Some(secs) => tokio::time::timeout(std::time::Duration::from_secs(secs), next)
+                        .await
+                        .ok()
+                        .flatten(),
+                    None => next.await,
+                }
+            } else {
+                history.latest().await
+            };
+            match entry {
+                Some(entry) => ControlResponse {
+                    content: Some(entry.content),
+                    ..ControlResponse::ok("")
+                },
+                None if wait => ControlResponse::error("等待超时，没有收到新内容"),
+                None => ControlResponse::error("还没有同步过的内容"),
+            }
+        }
+        ControlRequest::ListHistory => ControlResponse {
+            history: network.history().entries().await,
+            ..ControlResponse::ok("")
+        },
+        ControlRequest::PinHistory { hash, pinned } => match network.history().set_pinned(&hash, pinned).await {
+            true if pinned => ControlResponse::ok("已固定"),
+            true => ControlResponse::ok("已取消固定"),
+            false => ControlResponse::error("历史记录中没有该内容"),
+        },
+        ControlRequest::DeleteHistory { hash } => match network.history().remove(&hash).await {
+            true => ControlResponse::ok("已删除"),
+            false => ControlResponse::error("历史记录中没有该内容"),
+        },
+        ControlRequest::RestoreHistory { hash, broadcast } => {
+            let Some(entry) = network.history().get(&hash).await else {
+                return ControlResponse::error("历史记录中没有该内容");
+            };
+
+            // 剪贴板监控会识别出这次写入而不广播，是否发给其他设备由 broadcast 决定
+            let content = entry.content.clone();
+            if let Err(e) = clipboard.run_blocking(move |clipboard| clipboard.set_content(&content)).await {
+                return ControlResponse::error(format!("写回剪贴板失败: {}", e));
+            }
+
+            // 原本设置了清除时间的内容，写回后仍按剩余时间清除
+            let remaining = entry.remaining_ttl();
+            if let Some(remaining) = remaining {
+                clipboard.clear_after(Selection::Clipboard, entry.content.clone(), remaining);
+            }
+            if !broadcast {
+                return ControlResponse::ok("已写回剪贴板");
+            }
+            match network.send_content(entry.content, &[], remaining).await {
+                Ok(delivered) => ControlResponse::ok(format!("已写回剪贴板，{}", describe_delivery(delivered))),
+                Err(e) => ControlResponse::error(format!("已写回剪贴板，但发送失败: {}", e)),
+            }
+        }
+        ControlRequest::SetSnippet { name, content } => match network.set_snippet(&name, content).await {
+            Ok(()) => ControlResponse::ok(format!("已保存片段: {}", name)),
+            Err(e) => ControlResponse::error(format!("保存片段失败: {}", e)),
+        },
+        ControlRequest::RemoveSnippet { name } => match network.remove_snippet(&name).await {
+            Ok(true) => ControlResponse::ok(format!("已删除片段: {}", name)),
+            Ok(false) => ControlResponse::error(format!("没有名为 {} 的片段", name)),
+            Err(e) => ControlResponse::error(format!("删除片段失败: {}", e)),
+        },
+        ControlRequest::CopySnippet { name } => {
+            let Some(content) = network.snippet(&name).await else {
+                return ControlResponse::error(format!("没有名为 {} 的片段", name));
+            };
+            // 其他设备已经有这个片段，剪贴板监控会识别出这次写入而不广播
+            match clipboard.run_blocking(move |clipboard| clipboard.set_content(&content)).await {
+                Ok(()) => ControlResponse::ok(format!("已将片段 {} 写入剪贴板", name)),
+                Err(e) => ControlResponse::error(format!("写入剪贴板失败: {}", e)),
+            }
+        }
+        ControlRequest::ReloadConfig => match reloader.reload().await {
+            Ok(message) => ControlResponse::ok(message),
+            Err(e) => ControlResponse::error(format!("配置重新加载失败，继续使用当前配置: {}", e)),
+        },
+    }
+}
+
+/// 发送结果的说明
+pub fn describe_delivery(delivered: Option<usize>) -> String {
+    match delivered {
+        Some(0) => "没有设备接收，内容未送达".to_string(),
+        Some(count) => format!("已发送到 {} 台设备", count),
+        None => "已发布到 gossip 主题".to_string(),
+    }
+}
+
+/// 将经过的秒数格式化为易读的时长
+pub fn format_elapsed(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{} 秒", secs),
+        60..=3599 => format!("{} 分钟", secs / 60),
+        3600..=86399 => format!("{} 小时", secs / 3600),
+        _ => format!("{} 天", secs / 86400),
+    }
+}
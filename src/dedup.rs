@@ -0,0 +1,67 @@
+use std::collections::{HashSet, VecDeque};
+
+/// 默认保留的已见消息 ID 数量
+pub const DEFAULT_SEEN_CAPACITY: usize = 1024;
+
+/// 有界的已见消息集合 - 用于丢弃重复投递的消息
+///
+/// 超出容量时按插入顺序淘汰最早的 ID。
+#[derive(Debug)]
+pub struct SeenMessages {
+    capacity: usize,
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl SeenMessages {
+    /// 创建指定容量的已见集合
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            ids: HashSet::new(),
+        }
+    }
+
+    /// 记录消息 ID，若此前未见过则返回 true
+    pub fn insert(&mut self, id: &str) -> bool {
+        if self.ids.contains(id) {
+            return false;
+        }
+
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+
+        self.order.push_back(id.to_string());
+        self.ids.insert(id.to_string());
+        true
+    }
+}
+
+impl Default for SeenMessages {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicates_are_rejected_and_oldest_evicted() {
+        let mut seen = SeenMessages::new(2);
+
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("a"));
+        assert!(seen.insert("b"));
+        assert!(seen.insert("c"));
+
+        // "a" 已被淘汰，可以再次插入
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("c"));
+    }
+}
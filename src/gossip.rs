@@ -0,0 +1,98 @@
+use anyhow::Result;
+use futures_lite::StreamExt;
+use iroh::NodeId;
+use iroh_gossip::api::{Event, GossipSender};
+use iroh_gossip::net::Gossip;
+use iroh_gossip::proto::TopicId;
+use tracing::{info, warn};
+
+use crate::network::{ClipboardMessage, ClipboardProtocol};
+
+/// gossip 单条消息的最大字节数
+pub const GOSSIP_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// 由共享的组密钥派生 gossip 主题 ID
+///
+/// 持有相同密钥的设备会加入同一个主题，彼此不需要知道对方的全部节点。
+pub fn topic_from_secret(secret: &[u8]) -> TopicId {
+    TopicId::from_bytes(blake3::derive_key("clipboard-sync gossip topic v1", secret))
+}
+
+/// 基于 iroh-gossip 的组同步传输
+#[derive(Debug, Clone)]
+pub struct GossipTransport {
+    sender: GossipSender,
+}
+
+impl GossipTransport {
+    /// 加入主题，并在后台把收到的消息交给剪贴板协议处理
+    pub async fn join(
+        gossip: &Gossip,
+        topic: TopicId,
+        bootstrap: Vec<NodeId>,
+        protocol: ClipboardProtocol,
+    ) -> Result<Self> {
+        let (sender, mut receiver) = gossip
+            .subscribe(topic, bootstrap)
+            .await
+            .map_err(|e| anyhow::anyhow!("加入 gossip 主题失败: {}", e))?
+            .split();
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.next().await {
+                match event {
+                    Ok(Event::Received(msg)) => match ClipboardMessage::from_bytes(&msg.content) {
+                        Ok(message) => {
+                            protocol.deliver(message, Some(msg.delivered_from)).await;
+                        }
+                        Err(e) => {
+                            warn!("gossip 消息解析失败: {}", e);
+                        }
+                    },
+                    Ok(Event::NeighborUp(node_id)) => {
+                        info!("✅ gossip 邻居上线: {}", node_id);
+                    }
+                    Ok(Event::NeighborDown(node_id)) => {
+                        info!("gossip 邻居离线: {}", node_id);
+                    }
+                    Ok(Event::Lagged) => {
+                        warn!("gossip 消息处理过慢，部分消息已丢失");
+                    }
+                    Err(e) => {
+                        warn!("gossip 接收失败: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+
+    /// 向主题内的所有设备广播数据
+    pub async fn broadcast(&self, data: Vec<u8>) -> Result<()> {
+        self.sender
+            .broadcast(data.into())
+            .await
+            .map_err(|e| anyhow::anyhow!("gossip 广播失败: {}", e))
+    }
+
+    /// 将节点加入主题的引导列表
+    pub async fn join_peers(&self, peers: Vec<NodeId>) -> Result<()> {
+        self.sender
+            .join_peers(peers)
+            .await
+            .map_err(|e| anyhow::anyhow!("加入 gossip 节点失败: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_is_derived_from_secret() {
+        assert_eq!(topic_from_secret("家里".as_bytes()), topic_from_secret("家里".as_bytes()));
+        assert_ne!(topic_from_secret("家里".as_bytes()), topic_from_secret("公司".as_bytes()));
+    }
+}
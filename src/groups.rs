@@ -0,0 +1,184 @@
+use anyhow::Result;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::storage;
+
+/// 同步组 - 拥有独立密钥和成员的一组设备
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncGroup {
+    pub name: String,
+    #[serde(with = "hex::serde")]
+    pub key: [u8; 32], // 组密钥，只在成员之间共享
+    #[serde(default)]
+    pub members: Vec<String>, // 已证明持有组密钥的节点 ID
+}
+
+impl SyncGroup {
+    /// 创建带随机密钥的新组
+    pub fn generate(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            key: rand::random(),
+            members: Vec::new(),
+        }
+    }
+
+    /// 组密钥的十六进制形式，用于邀请其他设备加入
+    pub fn key_hex(&self) -> String {
+        hex::encode(self.key)
+    }
+
+    /// 生成本节点的组成员证明
+    pub fn membership_proof(&self, node_id: &NodeId) -> String {
+        self.keyed_hash(&[b"member:".as_slice(), node_id.as_bytes()])
+            .to_hex()
+            .to_string()
+    }
+
+    /// 校验对方节点的组成员证明
+    pub fn verify_membership(&self, node_id: &NodeId, proof: &str) -> bool {
+        let expected = self.keyed_hash(&[b"member:".as_slice(), node_id.as_bytes()]);
+        blake3::Hash::from_hex(proof).is_ok_and(|proof| proof == expected)
+    }
+
+    /// 计算消息的组标签，证明消息来自组成员
+    pub fn message_tag(&self, message_id: &str, content_hash: &str) -> String {
+        self.keyed_hash(&[
+            b"message:".as_slice(),
+            message_id.as_bytes(),
+            b":",
+            content_hash.as_bytes(),
+        ])
+        .to_hex()
+        .to_string()
+    }
+
+    /// 校验消息的组标签
+    pub fn verify_message_tag(&self, message_id: &str, content_hash: &str, tag: &str) -> bool {
+        let expected = self.keyed_hash(&[
+            b"message:".as_slice(),
+            message_id.as_bytes(),
+            b":",
+            content_hash.as_bytes(),
+        ]);
+        blake3::Hash::from_hex(tag).is_ok_and(|tag| tag == expected)
+    }
+
+    /// 在局域网发现中声明的组标识，只有持有组密钥的设备能识别
+    pub fn discovery_tag(&self) -> String {
+        hex::encode(&self.keyed_hash(&[b"discovery".as_slice()]).as_bytes()[..8])
+    }
+
+    fn keyed_hash(&self, parts: &[&[u8]]) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize()
+    }
+}
+
+/// 本地保存的同步组列表
+pub struct GroupStore {
+    path: PathBuf,
+    groups: Vec<SyncGroup>,
+}
+
+impl GroupStore {
+    /// 从配置目录加载同步组
+    pub fn load() -> Result<Self> {
+        let path = storage::config_dir()?.join("groups.json");
+        let groups = storage::load_json(&path)?;
+        Ok(Self { path, groups })
+    }
+
+    /// 保存同步组
+    pub fn save(&self) -> Result<()> {
+        storage::save_json(&self.path, &self.groups)
+    }
+
+    /// 所有已加入的组
+    pub fn groups(&self) -> &[SyncGroup] {
+        &self.groups
+    }
+
+    /// 按名称查找组
+    pub fn get(&self, name: &str) -> Option<&SyncGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// 创建新组
+    pub fn create(&mut self, name: &str) -> Result<SyncGroup> {
+        if self.get(name).is_some() {
+            anyhow::bail!("同步组已存在: {}", name);
+        }
+        let group = SyncGroup::generate(name);
+        self.groups.push(group.clone());
+        self.save()?;
+        Ok(group)
+    }
+
+    /// 使用组密钥加入已有的组
+    pub fn join(&mut self, name: &str, key_hex: &str) -> Result<()> {
+        let mut key = [0u8; 32];
+        hex::decode_to_slice(key_hex.trim(), &mut key)
+            .map_err(|e| anyhow::anyhow!("组密钥格式错误: {}", e))?;
+
+        match self.groups.iter_mut().find(|group| group.name == name) {
+            Some(group) => group.key = key,
+            None => self.groups.push(SyncGroup {
+                name: name.to_string(),
+                key,
+                members: Vec::new(),
+            }),
+        }
+        self.save()
+    }
+
+    /// 离开组
+    pub fn leave(&mut self, name: &str) -> Result<()> {
+        let before = self.groups.len();
+        self.groups.retain(|group| group.name != name);
+        if self.groups.len() == before {
+            anyhow::bail!("未加入同步组: {}", name);
+        }
+        self.save()
+    }
+
+    /// 记录已证明身份的组成员，返回是否为新成员
+    pub fn add_member(&mut self, name: &str, node_id: &NodeId) -> Result<bool> {
+        let node_id = node_id.to_string();
+        let Some(group) = self.groups.iter_mut().find(|group| group.name == name) else {
+            return Ok(false);
+        };
+        if group.members.contains(&node_id) {
+            return Ok(false);
+        }
+        group.members.push(node_id);
+        self.save()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_membership_proof_and_message_tag() {
+        let group = SyncGroup::generate("办公室");
+        let other = SyncGroup::generate("办公室");
+        let node_id = iroh::SecretKey::from_bytes(&[7u8; 32]).public();
+
+        let proof = group.membership_proof(&node_id);
+        assert!(group.verify_membership(&node_id, &proof));
+        assert!(!other.verify_membership(&node_id, &proof));
+
+        let tag = group.message_tag("id-1", "hash");
+        assert!(group.verify_message_tag("id-1", "hash", &tag));
+        assert!(!group.verify_message_tag("id-2", "hash", &tag));
+        assert!(!other.verify_message_tag("id-1", "hash", &tag));
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::clipboard::ClipboardBackend;
+use crate::network::ClipboardContent;
+use crate::storage;
+
+/// 只保存在内存中的剪贴板，用于没有图形界面的服务器和容器
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    content: Mutex<Option<ClipboardContent>>,
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&self) -> Result<String> {
+        text_of(self.content.lock().unwrap().as_ref())
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        *self.content.lock().unwrap() = Some(ClipboardContent::Text(text.to_string()));
+        Ok(())
+    }
+
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        Ok(image_of(self.content.lock().unwrap().as_ref()))
+    }
+
+    fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()> {
+        *self.content.lock().unwrap() = Some(ClipboardContent::Image {
+            width,
+            height,
+            data: png_data.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        *self.content.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// 保存在文件中的剪贴板
+///
+/// 文件中只保存最新的一条内容：文字按 UTF-8 写入，图片写入 PNG 数据。
+/// 其他程序修改文件后，新内容会像本机复制一样广播出去。
+#[derive(Debug)]
+pub struct FileClipboard {
+    path: PathBuf,
+    /// 按修改时间缓存解析结果，避免每次轮询都重新解码图片
+    cache: Mutex<Option<(SystemTime, Option<ClipboardContent>)>>,
+}
+
+impl FileClipboard {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cache: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> Result<Option<ClipboardContent>> {
+        let modified = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("读取 {} 失败: {}", self.path.display(), e)),
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if let (Some(modified), Some((cached_at, content))) = (modified, cache.as_ref()) {
+            if *cached_at == modified {
+                return Ok(content.clone());
+            }
+        }
+
+        let data = std::fs::read(&self.path)
+            .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", self.path.display(), e))?;
+        let content = if data.is_empty() {
+            None
+        } else {
+            Some(ClipboardContent::from_input(data)?)
+        };
+        if let Some(modified) = modified {
+            *cache = Some((modified, content.clone()));
+        }
+        Ok(content)
+    }
+
+    /// 同步过来的内容可能包含敏感信息，文件只允许当前用户读写
+    fn write(&self, data: &[u8]) -> Result<()> {
+        storage::write_private(&self.path, data)?;
+        *self.cache.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+impl ClipboardBackend for FileClipboard {
+    fn get_text(&self) -> Result<String> {
+        text_of(self.read()?.as_ref())
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        self.write(text.as_bytes())
+    }
+
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        Ok(image_of(self.read()?.as_ref()))
+    }
+
+    fn set_image(&self, _width: u32, _height: u32, png_data: &[u8]) -> Result<()> {
+        self.write(png_data)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.write(&[])
+    }
+}
+
+/// 与系统剪贴板一致：当前内容是图片时读取文字失败
+fn text_of(content: Option<&ClipboardContent>) -> Result<String> {
+    match content {
+        Some(ClipboardContent::Text(text)) => Ok(text.clone()),
+        Some(ClipboardContent::Image { .. }) => Err(anyhow::anyhow!("剪贴板中没有文字")),
+        None => Ok(String::new()),
+    }
+}
+
+fn image_of(content: Option<&ClipboardContent>) -> Option<(u32, u32, Vec<u8>)> {
+    match content {
+        Some(ClipboardContent::Image { width, height, data }) => Some((*width, *height, data.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_clipboard_round_trip() {
+        let path = std::env::temp_dir().join(format!("clipboard-sync-test-{}", std::process::id()));
+        let clipboard = FileClipboard::new(path.clone());
+        assert_eq!(clipboard.get_text().unwrap(), "");
+
+        clipboard.set_text("你好").unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "你好");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(clipboard.get_image().unwrap().is_none());
+
+        clipboard.clear().unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "");
+        let _ = std::fs::remove_file(path);
+
+        // 同名但扩展名为 tmp 的文件不会被覆盖
+        let dir = std::env::temp_dir().join(format!("clipboard-sync-test-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("clip.tmp"), "其他程序的文件").unwrap();
+        FileClipboard::new(dir.join("clip.txt")).set_text("你好").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("clip.tmp")).unwrap(), "其他程序的文件");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
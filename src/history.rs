@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::clipboard::Selection;
+use crate::network::{ClipboardContent, ClipboardMessage};
+
+/// 默认保留的历史条数
+pub const DEFAULT_HISTORY_CAPACITY: usize = 100;
+
+/// 历史内容的来源
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistorySource {
+    /// 本机复制或发送的内容
+    Local,
+    /// 其他设备同步过来的内容，记录设备名
+    Remote(String),
+}
+
+/// 一条同步历史
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub content: ClipboardContent,
+    pub content_hash: String,
+    pub source: HistorySource,
+    pub timestamp: u64, // Unix 时间戳
+    #[serde(default)]
+    pub pinned: bool, // 固定的条目不会因超出容量被移除
+    #[serde(default)]
+    pub expires_at: Option<u64>, // 设置了清除时间的内容到期后从历史中移除，Unix 时间戳
+}
+
+impl HistoryEntry {
+    /// 距离到期的剩余时间，没有设置清除时间时返回 None
+    pub fn remaining_ttl(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| Duration::from_secs(expires_at.saturating_sub(unix_now())))
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// 最近同步过的内容，只保存在内存中
+///
+/// 隐身模式下的内容、疑似敏感的内容和随选中文字变化的 PRIMARY 选区内容不会被记录，
+/// 设置了清除时间的内容到期后不再出现在历史中。
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: Arc<Mutex<VecDeque<HistoryEntry>>>,
+    capacity: usize,
+    updates: broadcast::Sender<HistoryEntry>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        let (updates, _) = broadcast::channel(16);
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            capacity,
+            updates,
+        }
+    }
+
+    /// 记录一条同步的内容，返回是否已记录
+    ///
+    /// 与已有条目内容相同时只移动到最前面，不重复保存。
+    pub async fn record(&self, message: &ClipboardMessage, source: HistorySource) -> bool {
+        if message.incognito || message.sensitive || message.selection == Selection::Primary {
+            return false;
+        }
+
+        let mut entry = HistoryEntry {
+            content: message.content.clone(),
+            content_hash: message.content_hash.clone(),
+            source,
+            timestamp: message.timestamp,
+            pinned: false,
+            expires_at: message.ttl_secs.map(|ttl| unix_now() + ttl),
+        };
+
+        {
+            let mut entries = self.lock_entries().await;
+            if let Some(index) = entries.iter().position(|existing| existing.content_hash == entry.content_hash) {
+                entry.pinned = entries.remove(index).is_some_and(|existing| existing.pinned);
+            }
+            entries.push_front(entry.clone());
+
+            // 超出容量时从最旧的未固定条目开始移除
+            while entries.len() > self.capacity {
+                let Some(index) = entries.iter().rposition(|existing| !existing.pinned) else {
+                    break;
+                };
+                entries.remove(index);
+            }
+        }
+
+        // 没有订阅者时发送失败，可以忽略
+        let _ = self.updates.send(entry);
+        true
+    }
+
+    /// 最新的一条
+    pub async fn latest(&self) -> Option<HistoryEntry> {
+        self.lock_entries().await.front().cloned()
+    }
+
+    /// 全部条目，最新的在前
+    pub async fn entries(&self) -> Vec<HistoryEntry> {
+        self.lock_entries().await.iter().cloned().collect()
+    }
+
+    /// 按内容哈希查找条目
+    pub async fn get(&self, content_hash: &str) -> Option<HistoryEntry> {
+        self.lock_entries()
+            .await
+            .iter()
+            .find(|entry| entry.content_hash == content_hash)
+            .cloned()
+    }
+
+    /// 固定或取消固定条目，返回是否找到
+    pub async fn set_pinned(&self, content_hash: &str, pinned: bool) -> bool {
+        let mut entries = self.lock_entries().await;
+        match entries.iter_mut().find(|entry| entry.content_hash == content_hash) {
+            Some(entry) => {
+                entry.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 删除条目，返回是否找到
+    pub async fn remove(&self, content_hash: &str) -> bool {
+        let mut entries = self.lock_entries().await;
+        let len = entries.len();
+        entries.retain(|entry| entry.content_hash != content_hash);
+        entries.len() != len
+    }
+
+    /// 订阅之后新记录的条目
+    pub fn subscribe(&self) -> broadcast::Receiver<HistoryEntry> {
+        self.updates.subscribe()
+    }
+
+    /// 取得条目列表，同时移除已到期的条目（固定的条目也不例外）
+    async fn lock_entries(&self) -> tokio::sync::MutexGuard<'_, VecDeque<HistoryEntry>> {
+        let mut entries = self.entries.lock().await;
+        let now = unix_now();
+        entries.retain(|entry| !entry.is_expired(now));
+        entries
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_skips_hidden_and_deduplicates() {
+        let history = History::new(2);
+        let text = |s: &str| ClipboardMessage::new(ClipboardContent::Text(s.to_string()), "测试".to_string());
+
+        assert!(history.record(&text("a"), HistorySource::Local).await);
+        assert!(history.record(&text("b"), HistorySource::Remote("笔记本".to_string())).await);
+        assert!(history.record(&text("a"), HistorySource::Local).await);
+        let hashes: Vec<String> = history.entries.lock().await.iter().map(|e| e.content_hash.clone()).collect();
+        assert_eq!(hashes, vec![text("a").content_hash, text("b").content_hash]);
+
+        assert!(history.record(&text("c"), HistorySource::Local).await);
+        assert_eq!(history.entries.lock().await.len(), 2);
+
+        let mut hidden = text("d");
+        hidden.incognito = true;
+        assert!(!history.record(&hidden, HistorySource::Local).await);
+        let mut secret = text("e");
+        secret.sensitive = true;
+        assert!(!history.record(&secret, HistorySource::Local).await);
+        assert_eq!(history.latest().await.unwrap().content_hash, text("c").content_hash);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_entries_survive_eviction() {
+        let history = History::new(2);
+        let text = |s: &str| ClipboardMessage::new(ClipboardContent::Text(s.to_string()), "测试".to_string());
+
+        history.record(&text("a"), HistorySource::Local).await;
+        assert!(history.set_pinned(&text("a").content_hash, true).await);
+        history.record(&text("b"), HistorySource::Local).await;
+        history.record(&text("c"), HistorySource::Local).await;
+        history.record(&text("a"), HistorySource::Local).await;
+
+        let entries = history.entries().await;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].content_hash, text("a").content_hash);
+        assert!(entries[0].pinned);
+        assert_eq!(entries[1].content_hash, text("c").content_hash);
+
+        assert!(history.remove(&text("a").content_hash).await);
+        assert!(!history.remove(&text("a").content_hash).await);
+        assert!(history.get(&text("a").content_hash).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_dropped() {
+        let history = History::new(10);
+        let mut expiring = ClipboardMessage::new(ClipboardContent::Text("一次性验证码".to_string()), "测试".to_string());
+        expiring.ttl_secs = Some(60);
+        assert!(history.record(&expiring, HistorySource::Local).await);
+
+        let entry = history.get(&expiring.content_hash).await.unwrap();
+        assert!(entry.remaining_ttl().is_some_and(|ttl| ttl <= Duration::from_secs(60)));
+
+        // 模拟已经到期
+        history.entries.lock().await[0].expires_at = Some(unix_now() - 1);
+        assert!(history.get(&expiring.content_hash).await.is_none());
+        assert!(history.entries().await.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+use crate::network::ClipboardContent;
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// 便于阅读的文本
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象，便于日志系统采集
+    Json,
+}
+
+/// 日志中剪贴板内容的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LogContent {
+    /// 只输出类型、长度和哈希
+    #[default]
+    Redacted,
+    /// 输出完整内容，仅用于调试
+    Full,
+}
+
+static CONTENT_MODE: OnceLock<LogContent> = OnceLock::new();
+
+/// 初始化全局日志
+///
+/// 日志写到标准错误，标准输出留给票据、设备列表等命令结果。
+/// 设置了 `RUST_LOG` 时以它为准，否则只输出本程序指定级别的日志和依赖库的警告。
+pub fn init(level: LevelFilter, format: LogFormat, content: LogContent) -> Result<()> {
+    let _ = CONTENT_MODE.set(content);
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,clipboard_sync={}", level)));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+
+    match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    }
+    .map_err(|e| anyhow::anyhow!("日志初始化失败: {}", e))
+}
+
+/// 用于日志的内容摘要
+///
+/// 默认只输出类型、长度和内容哈希前缀；即使开启了完整输出，敏感内容也会被隐藏。
+pub fn content_summary(content: &ClipboardContent, sensitive: bool) -> String {
+    let full = CONTENT_MODE.get().copied().unwrap_or_default() == LogContent::Full;
+    match content {
+        ClipboardContent::Text(text) if full && !sensitive => format!("文本: {}", text),
+        ClipboardContent::Text(text) => format!(
+            "文本, {} 个字符, 哈希 {}",
+            text.chars().count(),
+            short_hash(content)
+        ),
+        ClipboardContent::Image { width, height, data } => format!(
+            "图片 {}x{}, {} 字节, 哈希 {}",
+            width,
+            height,
+            data.len(),
+            short_hash(content)
+        ),
+    }
+}
+
+fn short_hash(content: &ClipboardContent) -> String {
+    content.hash()[..12].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_summary_hides_text() {
+        let content = ClipboardContent::Text("我的密码是 hunter2".to_string());
+        let summary = content_summary(&content, false);
+        assert!(!summary.contains("hunter2"));
+        assert!(summary.contains("13 个字符"));
+        assert!(summary.contains(&content.hash()[..12]));
+    }
+}
@@ -1,122 +1,1006 @@
+mod announce;
 mod clipboard;
+mod config;
+mod concealed;
+mod control;
+mod dedup;
+mod gossip;
+mod groups;
+mod headless;
+mod history;
+mod logging;
 mod network;
 mod notification;
+mod osc52;
+mod pause;
+mod pairing;
+mod primary;
+mod qr;
+mod reload;
+mod sensitive;
+mod snippets;
+mod storage;
+mod ticket;
+mod trust;
+mod tui;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+mod wayland;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use clipboard::ClipboardManager;
-use network::NetworkManager;
+use clap::{Args, Parser, Subcommand};
+use clipboard::{ClipboardManager, Selection};
+use config::{Config, ConfigOverrides, ConfigSource, PrimarySettings};
+use control::ControlRequest;
+use groups::GroupStore;
+use logging::{LogContent, LogFormat};
+use network::{ClipboardMessage, NetworkManager, NetworkOptions, TransportMode};
 use notification::NotificationManager;
-use std::time::Duration;
+use osc52::Osc52Clipboard;
+use reload::ConfigReloader;
+use pause::PauseDirection;
+use primary::{Debouncer, PrimaryFallback};
+use sensitive::SensitivePolicy;
+use snippets::SnippetStore;
+use trust::{ContentKind, PeerPolicy, SyncDirection, TrustStore};
+use iroh::NodeId;
+use network::ClipboardContent;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, error, info, warn};
+
+/// 单次发送或接收时连接其他设备的最长时间
+const PEER_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
 #[command(name = "clipboard-sync")]
 #[command(about = "跨平台剪贴板同步工具")]
 struct Cli {
-    /// 设备名称
-    #[arg(short, long, default_value = "我的设备")]
-    name: String,
+    /// 配置文件路径，默认使用配置目录下的 config.toml
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// 使用配置文件中的 profile（如 home、work）
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// 设备名称（默认“我的设备”）
+    #[arg(short, long)]
+    name: Option<String>,
+
+    /// 将收到的消息转发给其他已连接的设备（多跳中继）
+    #[arg(long, overrides_with = "no_relay")]
+    relay: bool,
+
+    /// 不转发收到的消息，覆盖配置文件中的 relay
+    #[arg(long, overrides_with = "relay")]
+    no_relay: bool,
+
+    /// 消息最多被转发的跳数（默认 3）
+    #[arg(long)]
+    max_hops: Option<u8>,
+
+    /// 传输模式（默认 direct）
+    #[arg(long, value_enum)]
+    transport: Option<TransportMode>,
+
+    /// gossip 模式下共享的组密钥，持有相同密钥的设备组成同一个同步组
+    #[arg(long)]
+    group_secret: Option<String>,
+
+    /// 参与同步的命名组（可多次指定），消息只会在组成员之间同步
+    #[arg(long = "group", value_name = "NAME")]
+    groups: Vec<String>,
+
+    /// 复制了疑似密码、密钥等敏感内容时的处理方式（默认 skip）
+    #[arg(long, value_enum)]
+    sensitive: Option<SensitivePolicy>,
+
+    /// 同步的内容在其他设备上保留的秒数，到期后自动清除
+    #[arg(long, value_name = "SECS")]
+    clear_after: Option<u64>,
+
+    /// 同步疑似敏感内容时在其他设备上保留的秒数，0 表示不自动清除（默认 60）
+    #[arg(long, value_name = "SECS")]
+    sensitive_clear_after: Option<u64>,
+
+    /// 以隐身模式启动，同步的内容不写入日志和历史
+    #[arg(long)]
+    incognito: bool,
+
+    /// 无界面模式：不访问系统剪贴板，只参与同步、转发和历史记录，适合服务器和容器
+    #[arg(long)]
+    headless: bool,
+
+    /// 无界面模式下把最新内容保存到该文件，不指定时只保存在内存中
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    clipboard_file: Option<PathBuf>,
+
+    /// 通过 OSC 52 转义序列把收到的文字写入当前终端的剪贴板，适合在 SSH 会话中运行
+    #[arg(long, conflicts_with = "headless")]
+    osc52: bool,
+
+    /// 广播本机 PRIMARY 选区（选中即复制、中键粘贴）的变化
+    #[arg(long, overrides_with = "no_primary_send")]
+    primary_send: bool,
+
+    /// 不广播 PRIMARY 选区，覆盖配置文件中的 primary.send
+    #[arg(long, overrides_with = "primary_send")]
+    no_primary_send: bool,
+
+    /// 应用其他设备发来的 PRIMARY 选区内容
+    #[arg(long, overrides_with = "no_primary_receive")]
+    primary_receive: bool,
+
+    /// 不应用其他设备发来的 PRIMARY 选区内容，覆盖配置文件中的 primary.receive
+    #[arg(long, overrides_with = "primary_receive")]
+    no_primary_receive: bool,
+
+    /// 日志级别（off、error、warn、info、debug、trace），设置了 RUST_LOG 时以其为准
+    #[arg(long, default_value_t = LevelFilter::INFO)]
+    log_level: LevelFilter,
+
+    /// 日志格式
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// 日志中剪贴板内容的输出方式
+    #[arg(long, value_enum, default_value_t = LogContent::Redacted)]
+    log_content: LogContent,
 
     #[command(subcommand)]
     command: Commands,
 }
 
+/// 票据二维码输出选项
+#[derive(Args, Clone, Default)]
+struct QrArgs {
+    /// 在终端中以二维码显示票据
+    #[arg(long)]
+    qr: bool,
+    /// 将票据二维码保存为 PNG 图片
+    #[arg(long, value_name = "PATH")]
+    qr_png: Option<PathBuf>,
+}
+
+impl Cli {
+    /// 配置文件、profile 和覆盖配置文件的命令行参数
+    fn config_source(&self) -> ConfigSource {
+        ConfigSource {
+            path: self.config.clone(),
+            profile: self.profile.clone(),
+            overrides: ConfigOverrides {
+                device_name: self.name.clone(),
+                groups: self.groups.clone(),
+                clear_after: self.clear_after,
+                sensitive_policy: self.sensitive,
+                sensitive_clear_after: self.sensitive_clear_after,
+                relay: flag(self.relay, self.no_relay),
+                max_hops: self.max_hops,
+                transport: self.transport,
+                group_secret: self.group_secret.clone(),
+                headless: self.headless,
+                primary_send: flag(self.primary_send, self.no_primary_send),
+                primary_receive: flag(self.primary_receive, self.no_primary_receive),
+            },
+        }
+    }
+}
+
+/// 成对的开关参数，两者都未指定时沿用配置文件
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// 启动同步服务（创建新网络）
-    Start,
+    Start {
+        #[command(flatten)]
+        qr: QrArgs,
+    },
     /// 连接到现有设备
     Connect {
-        /// 连接票据
+        /// 连接票据或配对码（如 7-crimson-harbor）
         ticket: String,
     },
     /// 生成连接票据
-    Ticket,
+    Ticket {
+        /// 有效期（分钟）
+        #[arg(long, default_value_t = 60)]
+        ttl: u64,
+        /// 票据只能使用一次
+        #[arg(long)]
+        single_use: bool,
+        #[command(flatten)]
+        qr: QrArgs,
+    },
+    /// 生成简短的配对码，供局域网内的设备输入后连接（需要同步服务正在运行）
+    Code {
+        /// 有效期（分钟）
+        #[arg(long, default_value_t = 5)]
+        ttl: u64,
+    },
+    /// 将标准输入或文件中的文本、图片发送给其他设备（如 `make 2>&1 | clipboard-sync send`）
+    Send {
+        /// 读取内容的文件，不指定时读取标准输入
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+        /// 只发送给指定设备（节点 ID，可多次指定）
+        #[arg(long = "peer", value_name = "ID")]
+        peers: Vec<NodeId>,
+    },
+    /// 将最新同步的内容写到标准输出或文件，图片保存为 PNG
+    #[command(alias = "paste")]
+    Recv {
+        /// 等待其他设备发来的下一条内容，而不是取最新一条（同步服务未运行时总是等待）
+        #[arg(long)]
+        wait: bool,
+        /// 最多等待的秒数
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        /// 写入的文件，不指定时写到标准输出
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// 浏览最近同步过的内容，可搜索、固定、删除并写回剪贴板（需要同步服务正在运行）
+    History {
+        /// 回车写回剪贴板时同时发给其他设备
+        #[arg(long)]
+        broadcast: bool,
+    },
+    /// 管理在可信设备之间同步的常用片段（地址、模板、命令等）
+    Snippet {
+        #[command(subcommand)]
+        action: SnippetCommands,
+    },
     /// 自动搜索其他设备
     Auto,
     /// 测试剪贴板功能
     Test,
+    /// 管理同步组
+    Group {
+        #[command(subcommand)]
+        action: GroupCommands,
+    },
+    /// 管理发现的设备和可信设备
+    Devices {
+        #[command(subcommand)]
+        action: DeviceCommands,
+    },
+    /// 处理等待确认的敏感内容（--sensitive ask 时使用）
+    Sensitive {
+        #[command(subcommand)]
+        action: SensitiveCommands,
+    },
+    /// 暂停同步（需要同步服务正在运行）
+    Pause {
+        /// 暂停的分钟数，不指定时直到手动恢复
+        #[arg(long = "for", value_name = "MINUTES")]
+        minutes: Option<u64>,
+        /// 暂停的方向
+        #[arg(long, value_enum, default_value_t = PauseDirection::Both)]
+        direction: PauseDirection,
+    },
+    /// 恢复同步
+    Resume {
+        /// 恢复的方向
+        #[arg(long, value_enum, default_value_t = PauseDirection::Both)]
+        direction: PauseDirection,
+    },
+    /// 开启或关闭隐身模式，隐身时同步的内容不写入日志和历史
+    Incognito {
+        #[arg(value_enum)]
+        state: Switch,
+    },
+    /// 查看暂停和隐身模式的状态
+    Status,
+    /// 让运行中的服务重新加载配置文件
+    Reload,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum SnippetCommands {
+    /// 列出全部片段
+    List,
+    /// 新建或修改片段，不指定内容和文件时读取标准输入
+    Set {
+        name: String,
+        /// 片段的文字内容
+        #[arg(conflicts_with = "file")]
+        text: Option<String>,
+        /// 读取内容的文件，可以是图片
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// 将片段写入剪贴板
+    Copy { name: String },
+    /// 删除片段
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+enum SensitiveCommands {
+    /// 同步等待确认的敏感内容
+    Allow,
+    /// 放弃同步等待确认的敏感内容
+    Deny,
+}
+
+#[derive(Subcommand)]
+enum DeviceCommands {
+    /// 列出发现的设备和可信设备
+    List,
+    /// 批准设备同步
+    Approve {
+        /// 发现列表中的编号或节点 ID
+        target: String,
+    },
+    /// 撤销对设备的信任
+    Revoke {
+        /// 节点 ID
+        node_id: String,
+    },
+    /// 设置与可信设备之间的同步方向和允许的内容类型
+    Policy {
+        /// 节点 ID
+        node_id: String,
+        /// 同步方向
+        #[arg(long, value_enum, default_value_t = SyncDirection::Bidirectional)]
+        direction: SyncDirection,
+        /// 允许同步的内容类型，多个类型用逗号分隔，默认全部允许
+        #[arg(long, value_enum, value_delimiter = ',')]
+        content: Vec<ContentKind>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupCommands {
+    /// 创建新的同步组
+    Create {
+        /// 组名
+        name: String,
+    },
+    /// 使用组密钥加入同步组
+    Join {
+        /// 组名
+        name: String,
+        /// 组密钥
+        key: String,
+    },
+    /// 离开同步组
+    Leave {
+        /// 组名
+        name: String,
+    },
+    /// 列出已加入的同步组
+    List,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    logging::init(cli.log_level, cli.log_format, cli.log_content)?;
+
+    // 同步组管理不需要剪贴板和网络
+    if let Commands::Group { action } = &cli.command {
+        return manage_groups(action);
+    }
+
+    // 设备管理优先交给运行中的服务处理
+    if let Commands::Devices { action } = &cli.command {
+        return manage_devices(action).await;
+    }
+
+    // 以下命令只能由运行中的服务处理
+    let request = match &cli.command {
+        Commands::Sensitive { action } => Some(ControlRequest::ResolveSensitive {
+            allow: matches!(action, SensitiveCommands::Allow),
+        }),
+        Commands::Pause { minutes, direction } => Some(ControlRequest::Pause {
+            direction: *direction,
+            minutes: *minutes,
+        }),
+        Commands::Resume { direction } => Some(ControlRequest::Resume { direction: *direction }),
+        Commands::Incognito { state } => Some(ControlRequest::SetIncognito {
+            enabled: matches!(state, Switch::On),
+        }),
+        Commands::Status => Some(ControlRequest::Status),
+        Commands::Reload => Some(ControlRequest::ReloadConfig),
+        _ => None,
+    };
+    if let Some(request) = request {
+        return match control::send_request(request).await? {
+            Some(response) if response.ok => {
+                println!("{}", response.message);
+                Ok(())
+            }
+            Some(response) => anyhow::bail!("{}", response.message),
+            None => anyhow::bail!("同步服务未运行"),
+        };
+    }
+
+    if let Commands::History { broadcast } = &cli.command {
+        return tui::run(*broadcast).await;
+    }
+
+    // 加载配置文件，命令行参数优先
+    let source = cli.config_source();
+    let config = source.load()?;
+    let device_name = config.device_name().to_string();
+    let options = network_options(&config, cli.incognito)?;
+
+    // 发送内容不需要访问本机剪贴板
+    if let Commands::Send { file, peers } = &cli.command {
+        let content = read_input(file.as_deref())?;
+        let request = ControlRequest::SendContent {
+            content: content.clone(),
+            peers: peers.clone(),
+        };
+        let message = match control::send_request(request).await? {
+            Some(response) if response.ok => response.message,
+            Some(response) => anyhow::bail!("{}", response.message),
+            None => send_once(device_name, options, content, peers).await?,
+        };
+        println!("{}", message);
+        return Ok(());
+    }
+
+    // 接收内容同样不需要访问本机剪贴板
+    if let Commands::Recv { wait, timeout, output } = &cli.command {
+        let request = ControlRequest::Receive {
+            wait: *wait,
+            timeout_secs: *timeout,
+        };
+        let content = match control::send_request(request).await? {
+            Some(response) if response.ok => response
+                .content
+                .ok_or_else(|| anyhow::anyhow!("同步服务没有返回内容"))?,
+            Some(response) => anyhow::bail!("{}", response.message),
+            None => receive_once(device_name, options, timeout.map(Duration::from_secs)).await?,
+        };
+        return write_output(&content, output.as_deref());
+    }
+
+    if let Commands::Snippet { action } = &cli.command {
+        return manage_snippets(&cli, action).await;
+    }
 
     // 初始化剪贴板管理器
-    let clipboard = ClipboardManager::new()?;
+    let clipboard = open_clipboard(&cli)?;
 
     match cli.command {
         Commands::Test => {
             test_clipboard(clipboard).await?;
         }
-        Commands::Start => {
-            let network = NetworkManager::new(cli.name.clone()).await?;
-            run_sync_service(clipboard, network).await?;
+        Commands::Start { qr } => {
+            let network = NetworkManager::new(device_name, options).await?;
+            run_sync_service(clipboard, network, &source, config, &qr).await?;
         }
         Commands::Connect { ticket } => {
-            let network = NetworkManager::new(cli.name.clone()).await?;
-            connect_to_peer(clipboard, network, &ticket).await?;
+            let network = NetworkManager::new(device_name, options).await?;
+            connect_to_peer(clipboard, network, &source, config, &ticket).await?;
         }
-        Commands::Ticket => {
-            let network = NetworkManager::new(cli.name.clone()).await?;
-            let ticket = network.generate_ticket().await?;
-            println!("连接票据:");
+        Commands::Ticket { ttl, single_use, qr } => {
+            let ttl = Duration::from_secs(ttl * 60);
+
+            // 服务运行时由服务签发，避免同一节点身份启动两个 endpoint
+            let request = ControlRequest::IssueTicket {
+                ttl_secs: ttl.as_secs(),
+                single_use,
+            };
+            let ticket = match control::send_request(request).await? {
+                Some(response) if response.ok => response.message,
+                Some(response) => anyhow::bail!("{}", response.message),
+                None => {
+                    let network = NetworkManager::new(device_name, options).await?;
+                    let ticket = network.generate_ticket(ttl, single_use).await?;
+                    network.shutdown().await;
+                    ticket
+                }
+            };
+            println!("连接票据（{} 分钟内有效{}）:", ttl.as_secs() / 60, if single_use { "，仅可使用一次" } else { "" });
             println!("{}", ticket);
             println!("\n在其他设备上运行以下命令来连接:");
             println!("clipboard-sync connect {}", ticket);
+            show_ticket_qr(&ticket, &qr)?;
+        }
+        Commands::Code { ttl } => {
+            let request = ControlRequest::IssuePairingCode { ttl_secs: ttl * 60 };
+            let code = match control::send_request(request).await? {
+                Some(response) if response.ok => response.message,
+                Some(response) => anyhow::bail!("{}", response.message),
+                None => anyhow::bail!("同步服务未运行，请先运行 clipboard-sync start 或 clipboard-sync auto"),
+            };
+            println!("配对码（{} 分钟内有效，仅可使用一次）: {}", ttl, code);
+            println!("\n在局域网内的其他设备上运行以下命令来连接:");
+            println!("clipboard-sync connect {}", code);
         }
         Commands::Auto => {
-            let network = NetworkManager::new(cli.name.clone()).await?;
-            auto_connect(clipboard, network).await?;
+            let network = NetworkManager::new(device_name, options).await?;
+            auto_connect(clipboard, network, &source, config).await?;
         }
+        Commands::Group { .. }
+        | Commands::Devices { .. }
+        | Commands::Sensitive { .. }
+        | Commands::Pause { .. }
+        | Commands::Resume { .. }
+        | Commands::Incognito { .. }
+        | Commands::Status
+        | Commands::Reload
+        | Commands::Send { .. }
+        | Commands::Recv { .. }
+        | Commands::History { .. }
+        | Commands::Snippet { .. } => unreachable!(),
     }
 
     Ok(())
 }
 
-async fn auto_connect(clipboard: ClipboardManager, network: NetworkManager) -> Result<()> {
-    let notifier = NotificationManager::new();
+/// 管理设备：服务运行时通过控制通道操作，否则直接修改可信设备列表
+async fn manage_devices(action: &DeviceCommands) -> Result<()> {
+    let request = match action {
+        DeviceCommands::List => ControlRequest::ListDevices,
+        DeviceCommands::Approve { target } => ControlRequest::ApproveDevice {
+            target: target.clone(),
+        },
+        DeviceCommands::Revoke { node_id } => ControlRequest::RevokeDevice {
+            node_id: node_id.clone(),
+        },
+        DeviceCommands::Policy {
+            node_id,
+            direction,
+            content,
+        } => ControlRequest::SetPeerPolicy {
+            node_id: node_id.clone(),
+            policy: peer_policy(*direction, content),
+        },
+    };
+
+    if let Some(response) = control::send_request(request).await? {
+        if !response.ok {
+            anyhow::bail!("{}", response.message);
+        }
+        println!("{}", response.message);
+        return Ok(());
+    }
 
-    println!("🔍 启动自动连接模式...");
+    let mut store = TrustStore::load()?;
+    match action {
+        DeviceCommands::List => {
+            println!("同步服务未运行，仅显示可信设备:");
+            if store.devices().is_empty() {
+                println!("  (无)");
+            }
+            for device in store.devices() {
+                println!(
+                    "  {} ({}) [{}]",
+                    device.device_name,
+                    device.node_id,
+                    device.policy.describe()
+                );
+            }
+        }
+        DeviceCommands::Approve { target } => {
+            let node_id = target
+                .parse()
+                .map_err(|_| anyhow::anyhow!("同步服务未运行时只能按节点 ID 批准: {}", target))?;
+            store.approve(&node_id, "未知设备")?;
+            println!("已批准设备: {}", node_id);
+        }
+        DeviceCommands::Revoke { node_id } => {
+            store.revoke(node_id)?;
+            println!("已撤销对设备的信任: {}", node_id);
+        }
+        DeviceCommands::Policy {
+            node_id,
+            direction,
+            content,
+        } => {
+            let policy = peer_policy(*direction, content);
+            let description = policy.describe();
+            let device_name = store.set_policy(node_id, policy)?;
+            println!("已更新 {} 的同步策略: {}", device_name, description);
+        }
+    }
+
+    Ok(())
+}
+
+/// 根据命令行参数构造设备同步策略，未指定内容类型时全部允许
+fn peer_policy(direction: SyncDirection, content: &[ContentKind]) -> PeerPolicy {
+    PeerPolicy {
+        direction,
+        content: if content.is_empty() {
+            ContentKind::ALL.to_vec()
+        } else {
+            content.to_vec()
+        },
+    }
+}
+
+/// 管理同步组
+fn manage_groups(action: &GroupCommands) -> Result<()> {
+    let mut store = GroupStore::load()?;
+
+    match action {
+        GroupCommands::Create { name } => {
+            let group = store.create(name)?;
+            println!("已创建同步组: {}", group.name);
+            println!("\n在其他设备上运行以下命令加入该组:");
+            println!("clipboard-sync group join {} {}", group.name, group.key_hex());
+            println!("\n启动同步时使用 --group {} 参与该组", group.name);
+        }
+        GroupCommands::Join { name, key } => {
+            store.join(name, key)?;
+            println!("已加入同步组: {}", name);
+        }
+        GroupCommands::Leave { name } => {
+            store.leave(name)?;
+            println!("已离开同步组: {}", name);
+        }
+        GroupCommands::List => {
+            if store.groups().is_empty() {
+                println!("尚未加入任何同步组");
+            }
+            for group in store.groups() {
+                println!("{} (已知成员: {})", group.name, group.members.len());
+                for member in &group.members {
+                    println!("  - {}", member);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 从文件或标准输入读取要发送的内容
+fn read_input(file: Option<&Path>) -> Result<ClipboardContent> {
+    let data = match file {
+        Some(path) => std::fs::read(path).map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", path.display(), e))?,
+        None => {
+            let mut data = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut data)
+                .map_err(|e| anyhow::anyhow!("读取标准输入失败: {}", e))?;
+            data
+        }
+    };
+    ClipboardContent::from_input(data)
+}
+
+/// 同步服务未运行时，临时连接可信设备发送内容后退出
+async fn send_once(
+    device_name: String,
+    options: NetworkOptions,
+    content: ClipboardContent,
+    peers: &[NodeId],
+) -> Result<String> {
+    if options.transport == TransportMode::Gossip {
+        anyhow::bail!("gossip 模式下请先启动同步服务，再用 send 发送");
+    }
+
+    // 未指定设备时发给所有可信设备
+    let targets = if peers.is_empty() {
+        trusted_peers(&options)?
+    } else {
+        peers.to_vec()
+    };
+    if targets.is_empty() {
+        anyhow::bail!("没有可发送的设备，请先批准设备或用 --peer 指定");
+    }
+
+    let network = NetworkManager::new(device_name, options).await?;
+    let connected = network.connect_peers(&targets, PEER_CONNECT_TIMEOUT).await;
+    let result = if connected.is_empty() {
+        Err(anyhow::anyhow!("无法连接到任何设备"))
+    } else {
+        network.send_content(content, &connected, None).await
+    };
+    network.shutdown().await;
+
+    Ok(control::describe_delivery(result?))
+}
+
+/// 同步服务未运行时，临时连接可信设备并等待下一条内容
+async fn receive_once(device_name: String, options: NetworkOptions, timeout: Option<Duration>) -> Result<ClipboardContent> {
+    if options.transport == TransportMode::Gossip {
+        anyhow::bail!("gossip 模式下请先启动同步服务，再用 recv 接收");
+    }
+    let targets = trusted_peers(&options)?;
+    if targets.is_empty() {
+        anyhow::bail!("还没有可信设备，请先批准设备");
+    }
+
+    let network = NetworkManager::new(device_name, options).await?;
+    let mut message_receiver = network.setup_message_handler().await;
+    let connected = network.connect_peers(&targets, PEER_CONNECT_TIMEOUT).await;
+
+    let result = if connected.is_empty() {
+        Err(anyhow::anyhow!("无法连接到任何设备"))
+    } else {
+        info!("同步服务未运行，等待其他设备发送新内容...");
+        let next = async {
+            // PRIMARY 选区的内容随选中文字变化，不作为接收结果
+            loop {
+                match message_receiver.recv().await {
+                    Some(message) if message.selection == Selection::Primary => continue,
+                    other => return other,
+                }
+            }
+        };
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, next)
+                .await
+                .map_err(|_| anyhow::anyhow!("等待超时，没有收到新内容")),
+            None => Ok(next.await),
+        }
+        .and_then(|message| message.ok_or_else(|| anyhow::anyhow!("连接已关闭")))
+    };
+    network.shutdown().await;
+
+    Ok(result?.content)
+}
+
+/// 按命令行参数选择剪贴板后端
+fn open_clipboard(cli: &Cli) -> Result<ClipboardManager> {
+    if cli.headless {
+        info!("无界面模式，不访问系统剪贴板");
+        Ok(ClipboardManager::headless(cli.clipboard_file.clone()))
+    } else if cli.osc52 {
+        info!("通过 OSC 52 写入终端剪贴板");
+        Ok(ClipboardManager::with_backend(Osc52Clipboard::new()))
+    } else {
+        ClipboardManager::new()
+    }
+}
+
+/// 管理片段：同步服务运行时交给服务处理并同步到其他设备，否则只修改本机的片段库，下次连接时再同步
+async fn manage_snippets(cli: &Cli, action: &SnippetCommands) -> Result<()> {
+    let request = match action {
+        SnippetCommands::List => {
+            let store = SnippetStore::load()?;
+            let snippets = store.list();
+            if snippets.is_empty() {
+                println!("还没有片段，使用 `clipboard-sync snippet set <名称> <内容>` 添加");
+            }
+            for snippet in snippets {
+                if let Some(content) = &snippet.content {
+                    println!("{}  {}", snippet.name, content.preview(50).replace('\n', " "));
+                }
+            }
+            return Ok(());
+        }
+        SnippetCommands::Set { name, text, file } => {
+            let content = match text {
+                Some(text) => ClipboardContent::Text(text.clone()),
+                None => read_input(file.as_deref())?,
+            };
+            ControlRequest::SetSnippet {
+                name: name.clone(),
+                content,
+            }
+        }
+        SnippetCommands::Copy { name } => ControlRequest::CopySnippet { name: name.clone() },
+        SnippetCommands::Remove { name } => ControlRequest::RemoveSnippet { name: name.clone() },
+    };
+
+    if let Some(response) = control::send_request(request.clone()).await? {
+        if !response.ok {
+            anyhow::bail!("{}", response.message);
+        }
+        println!("{}", response.message);
+        return Ok(());
+    }
+
+    let mut store = SnippetStore::load()?;
+    let node_id = network::local_node_id()?.to_string();
+    match request {
+        ControlRequest::SetSnippet { name, content } => {
+            store.set(&name, content, &node_id);
+            store.save()?;
+            println!("已保存片段: {}", name);
+        }
+        ControlRequest::RemoveSnippet { name } => {
+            if store.remove(&name, &node_id).is_none() {
+                anyhow::bail!("没有名为 {} 的片段", name);
+            }
+            store.save()?;
+            println!("已删除片段: {}", name);
+        }
+        ControlRequest::CopySnippet { name } => {
+            let content = store
+                .get(&name)
+                .ok_or_else(|| anyhow::anyhow!("没有名为 {} 的片段", name))?;
+            let content = content.clone();
+            let clipboard = open_clipboard(cli)?;
+            println!("已将片段 {} 写入剪贴板", name);
+            // X11 和 Wayland 上内容由本进程提供，退出后就会消失，需要一直运行到被其他内容替换
+            if cfg!(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))
+                && !cli.headless
+                && !cli.osc52
+            {
+                println!("被其他内容替换前保持运行，按 Ctrl+C 可提前退出");
+            }
+            with_clipboard(&clipboard, move |clipboard| clipboard.set_content_and_wait(&content)).await?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// 所有可信设备：已批准的设备和配置文件中声明的设备
+fn trusted_peers(options: &NetworkOptions) -> Result<Vec<NodeId>> {
+    let mut peers = options.rules.trusted_peers.clone();
+    for device in TrustStore::load()?.devices() {
+        if let Ok(node_id) = device.node_id.parse() {
+            if !peers.contains(&node_id) {
+                peers.push(node_id);
+            }
+        }
+    }
+    Ok(peers)
+}
+
+/// 将收到的内容写到文件或标准输出，图片为 PNG 数据
+fn write_output(content: &ClipboardContent, output: Option<&Path>) -> Result<()> {
+    let data = match content {
+        ClipboardContent::Text(text) => text.as_bytes(),
+        ClipboardContent::Image { data, .. } => data.as_slice(),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, data).map_err(|e| anyhow::anyhow!("写入 {} 失败: {}", path.display(), e))?;
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            if matches!(content, ClipboardContent::Image { .. }) && stdout.is_terminal() {
+                anyhow::bail!("收到的是图片，请用 --output 保存或重定向到文件");
+            }
+            stdout.write_all(data)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// 根据配置构造网络选项
+fn network_options(config: &Config, incognito: bool) -> Result<NetworkOptions> {
+    // 加载本次参与同步的组
+    let store = GroupStore::load()?;
+    let groups = config
+        .groups
+        .iter()
+        .map(|name| {
+            store
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("未加入同步组: {}", name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(NetworkOptions {
+        relay: config.transport.relay,
+        max_hops: config.transport.max_hops,
+        transport: config.transport.mode,
+        group_secret: config.transport.group_secret.clone(),
+        groups,
+        incognito,
+        local_discovery: config.transport.local_discovery,
+        rules: config.sync_rules(),
+    })
+}
+
+async fn auto_connect(
+    clipboard: ClipboardManager,
+    network: NetworkManager,
+    source: &ConfigSource,
+    config: Config,
+) -> Result<()> {
+    let notifier = NotificationManager::new(&config.notifications);
+
+    info!("🔍 启动自动连接模式...");
     notifier.send("剪贴板同步", "自动搜索其他设备中...")?;
 
     // 设置消息处理器
-    let mut message_receiver = network.setup_message_handler().await;
+    let message_receiver = network.setup_message_handler().await;
+
+    // 启动控制通道和配置文件监视
+    let reloader = spawn_control(&network, &clipboard, &notifier, source, config);
 
-    // 启动网络监听任务
-    // let network_clone = network.clone();
-    // tokio::spawn(async move {
-    //     if let Err(e) = network_clone.listen_for_connections().await {
-    //         eprintln!("网络监听失败: {}", e);
-    //     }
-    // });
+    // 在终端中接受批准命令
+    tokio::spawn(control::run_prompt(network.clone(), clipboard.clone(), reloader.clone()));
 
     // 启动自动发现任务
     let network_discovery = network.clone();
     tokio::spawn(async move {
         if let Err(e) = network_discovery.start_auto_discovery().await {
-            eprintln!("自动发现失败: {}", e);
+            error!("自动发现失败: {}", e);
         }
     });
 
     // 启动消息处理任务
-    let clipboard_clone = clipboard.clone();
-    let notifier_clone = notifier.clone();
+    spawn_message_receiver(clipboard.clone(), notifier.clone(), reloader.clone(), message_receiver);
+
+    info!("🌐 正在自动搜索局域网内的其他设备...");
+    println!("🔐 新设备需要批准后才会同步，输入 `list` 查看发现的设备");
+    info!("📋 监控剪贴板变化中...");
+    println!("按 Ctrl+C 停止服务");
+
+    // 剪贴板监控循环，直到按下 Ctrl+C
+    monitor_clipboard(&clipboard, &network, &reloader).await;
+
+    network.shutdown().await;
+    info!("自动连接服务已停止");
+
+    Ok(())
+}
+
+/// 启动控制通道，并在配置文件修改后自动重新加载
+fn spawn_control(
+    network: &NetworkManager,
+    clipboard: &ClipboardManager,
+    notifier: &NotificationManager,
+    source: &ConfigSource,
+    config: Config,
+) -> ConfigReloader {
+    let reloader = ConfigReloader::new(source.clone(), config, network.clone(), notifier.clone());
+    tokio::spawn(reloader.clone().watch());
+
+    let network = network.clone();
+    let clipboard = clipboard.clone();
+    let control_reloader = reloader.clone();
+    tokio::spawn(async move {
+        if let Err(e) = control::serve(network, clipboard, control_reloader).await {
+            error!("控制通道启动失败: {}", e);
+        }
+    });
+
+    reloader
+}
+
+/// 在后台接收其他设备发来的消息并写入本地剪贴板
+fn spawn_message_receiver(
+    clipboard: ClipboardManager,
+    notifier: NotificationManager,
+    reloader: ConfigReloader,
+    mut message_receiver: mpsc::UnboundedReceiver<ClipboardMessage>,
+) {
     tokio::spawn(async move {
         while let Some(message) = message_receiver.recv().await {
-            println!(
-                "收到剪贴板消息: {} (来自: {})",
-                message.content, message.sender_id
-            );
+            if message.selection == Selection::Primary {
+                apply_primary(&clipboard, &reloader.primary().await, &message).await;
+                continue;
+            }
 
             // 根据消息类型更新本地剪贴板
             match &message.content {
                 network::ClipboardContent::Text(text) => {
-                    if let Err(e) = clipboard_clone.set_text(text) {
-                        eprintln!("更新文本剪贴板失败: {}", e);
+                    let text = text.clone();
+                    if let Err(e) = clipboard.run_blocking(move |clipboard| clipboard.set_text(&text)).await {
+                        warn!("更新文本剪贴板失败: {}", e);
+                        continue;
                     } else {
-                        let preview = message.content.preview(50);
-                        let _ = notifier_clone.send("文本剪贴板已同步", &preview);
+                        let preview = if message.sensitive || message.incognito {
+                            "内容已隐藏".to_string()
+                        } else {
+                            message.content.preview(notifier.preview_length())
+                        };
+                        let _ = notifier.send("文本剪贴板已同步", &preview);
                     }
                 }
                 network::ClipboardContent::Image {
@@ -124,40 +1008,121 @@ async fn auto_connect(clipboard: ClipboardManager, network: NetworkManager) -> R
                     height,
                     data,
                 } => {
-                    if let Err(e) = clipboard_clone.set_image(*width, *height, data) {
-                        eprintln!("更新图片剪贴板失败: {}", e);
+                    let (width, height, data) = (*width, *height, data.clone());
+                    if let Err(e) = clipboard
+                        .run_blocking(move |clipboard| clipboard.set_image(width, height, &data))
+                        .await
+                    {
+                        warn!("更新图片剪贴板失败: {}", e);
+                        continue;
                     } else {
                         let preview = format!("图片 {}x{}", width, height);
-                        let _ = notifier_clone.send("图片剪贴板已同步", &preview);
+                        let _ = notifier.send("图片剪贴板已同步", &preview);
                     }
                 }
             }
+
+            // 发送方设置了有效期时，到期后清除仍未被替换的内容
+            if let Some(ttl) = message.ttl_secs {
+                info!("同步的内容将在 {} 秒后清除", ttl);
+                clipboard.clear_after(Selection::Clipboard, message.content, Duration::from_secs(ttl));
+            }
         }
     });
+}
 
-    println!("🌐 正在自动搜索局域网内的其他设备...");
-    println!("📋 监控剪贴板变化中...");
-    println!("按 Ctrl+C 停止服务");
+/// 应用其他设备发来的 PRIMARY 选区内容，本机不支持时按配置忽略或写入剪贴板
+async fn apply_primary(clipboard: &ClipboardManager, settings: &PrimarySettings, message: &ClipboardMessage) {
+    let network::ClipboardContent::Text(text) = &message.content else {
+        return;
+    };
+    if !settings.receive {
+        debug!("未开启 PRIMARY 选区接收，忽略收到的内容");
+        return;
+    }
+
+    let write = text.clone();
+    let (selection, result) = if clipboard.supports_primary() {
+        (Selection::Primary, clipboard.run_blocking(move |clipboard| clipboard.set_primary(&write)).await)
+    } else {
+        match settings.fallback {
+            PrimaryFallback::Ignore => {
+                debug!("本机不支持 PRIMARY 选区，忽略收到的内容");
+                return;
+            }
+            PrimaryFallback::Clipboard => (
+                Selection::Clipboard,
+                clipboard.run_blocking(move |clipboard| clipboard.set_text(&write)).await,
+            ),
+        }
+    };
+    if let Err(e) = result {
+        warn!("更新 PRIMARY 选区失败: {}", e);
+        return;
+    }
+
+    // 与剪贴板内容一样，到期后清除仍未被替换的选区
+    if let Some(ttl) = message.ttl_secs {
+        info!("同步的选区内容将在 {} 秒后清除", ttl);
+        clipboard.clear_after(selection, message.content.clone(), Duration::from_secs(ttl));
+    }
+}
 
-    // 剪贴板监控循环
+/// 在阻塞线程中访问剪贴板，X11、Wayland 等后端的同步调用不会卡住异步运行时
+async fn with_clipboard<T: Send + 'static>(
+    clipboard: &ClipboardManager,
+    f: impl FnOnce(&ClipboardManager) -> T + Send + 'static,
+) -> T {
+    let clipboard = clipboard.clone();
+    tokio::task::spawn_blocking(move || f(&clipboard))
+        .await
+        .expect("剪贴板任务异常退出")
+}
+
+/// 监控本地剪贴板变化并广播到其他设备，按下 Ctrl+C 时返回
+async fn monitor_clipboard(clipboard: &ClipboardManager, network: &NetworkManager, reloader: &ConfigReloader) {
     let mut last_text_content = String::new();
     let mut last_content_type = clipboard::ClipboardContentType::Empty;
+    let mut primary = Debouncer::default();
 
     loop {
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        tokio::time::sleep(reloader.poll_interval().await).await;
+
+        // PRIMARY 选区随选中文字不断变化，保持不变一段时间后才广播
+        let settings = reloader.primary().await;
+        if let Some(text) = clipboard.take_applied_primary() {
+            primary.seed(text);
+        }
+        if settings.send && clipboard.supports_primary() {
+            if let Ok(text) = with_clipboard(clipboard, ClipboardManager::get_primary).await {
+                if let Some(text) = primary.update(text, settings.debounce(), Instant::now()) {
+                    debug!(chars = text.chars().count(), "检测到 PRIMARY 选区变化");
+                    if let Err(e) = network.broadcast_clipboard(&text, Selection::Primary).await {
+                        warn!("PRIMARY 选区广播失败: {}", e);
+                    }
+                }
+            }
+        }
 
         // 检查剪贴板内容类型
-        let current_type = clipboard.get_content_type();
+        let current_type = with_clipboard(clipboard, ClipboardManager::get_content_type).await;
 
         match current_type {
             clipboard::ClipboardContentType::Text => {
-                if let Ok(current_content) = clipboard.get_text() {
+                if let Ok(current_content) = with_clipboard(clipboard, ClipboardManager::get_text).await {
                     if current_content != last_text_content && !current_content.is_empty() {
-                        println!("检测到文本剪贴板变化: {}", current_content);
-
-                        // 广播文本到其他设备
-                        if let Err(e) = network.broadcast_clipboard(&current_content).await {
-                            eprintln!("文本广播失败: {}", e);
+                        if clipboard.take_applied(&network::ClipboardContent::Text(current_content.clone())) {
+                            debug!("内容由本程序写入，不再广播");
+                        } else if with_clipboard(clipboard, ClipboardManager::is_concealed).await {
+                            // 密码管理器标记的内容既不广播也不记录
+                            info!("🔒 密码管理器标记了当前内容，已跳过同步");
+                        } else {
+                            debug!(chars = current_content.chars().count(), "检测到文本剪贴板变化");
+
+                            // 广播文本到其他设备
+                            if let Err(e) = network.broadcast_clipboard(&current_content, Selection::Clipboard).await {
+                                warn!("文本广播失败: {}", e);
+                            }
                         }
 
                         last_text_content = current_content;
@@ -168,12 +1133,20 @@ async fn auto_connect(clipboard: ClipboardManager, network: NetworkManager) -> R
             clipboard::ClipboardContentType::Image => {
                 // 只有当之前不是图片类型时才处理，避免重复处理
                 if !matches!(last_content_type, clipboard::ClipboardContentType::Image) {
-                    if let Ok(Some((width, height, png_data))) = clipboard.get_image() {
-                        println!("检测到图片剪贴板变化: {}x{}", width, height);
-
-                        // 广播图片到其他设备
-                        if let Err(e) = network.broadcast_image(width, height, png_data).await {
-                            eprintln!("图片广播失败: {}", e);
+                    if let Ok(Some((width, height, png_data))) = with_clipboard(clipboard, ClipboardManager::get_image).await {
+                        // 图片需要解码后按像素比较，同样放到阻塞线程中
+                        let content = network::ClipboardContent::Image { width, height, data: png_data.clone() };
+                        if with_clipboard(clipboard, move |clipboard| clipboard.take_applied(&content)).await {
+                            debug!("内容由本程序写入，不再广播");
+                        } else if with_clipboard(clipboard, ClipboardManager::is_concealed).await {
+                            info!("🔒 密码管理器标记了当前内容，已跳过同步");
+                        } else {
+                            debug!("检测到图片剪贴板变化: {}x{}", width, height);
+
+                            // 广播图片到其他设备
+                            if let Err(e) = network.broadcast_image(width, height, png_data).await {
+                                warn!("图片广播失败: {}", e);
+                            }
                         }
 
                         last_content_type = current_type;
@@ -197,11 +1170,6 @@ async fn auto_connect(clipboard: ClipboardManager, network: NetworkManager) -> R
             _ = tokio::time::sleep(Duration::from_millis(10)) => {}
         }
     }
-
-    network.shutdown().await;
-    println!("自动连接服务已停止");
-
-    Ok(())
 }
 
 /// 测试剪贴板功能
@@ -226,133 +1194,68 @@ async fn test_clipboard(manager: ClipboardManager) -> Result<()> {
     Ok(())
 }
 
+/// 按需以二维码形式输出票据，二维码中使用紧凑编码
+fn show_ticket_qr(ticket: &str, qr: &QrArgs) -> Result<()> {
+    if !qr.qr && qr.qr_png.is_none() {
+        return Ok(());
+    }
+    let compact = ticket::ConnectionTicket::from_string(ticket)?.to_compact_string()?;
+
+    if qr.qr {
+        println!("\n扫描二维码获取票据:");
+        println!("{}", qr::render_terminal(&compact)?);
+    }
+    if let Some(path) = &qr.qr_png {
+        qr::save_png(&compact, path)?;
+        println!("二维码已保存到: {}", path.display());
+    }
+    println!("紧凑票据: {}", compact);
+    Ok(())
+}
+
 /// 运行同步服务
-async fn run_sync_service(clipboard: ClipboardManager, network: NetworkManager) -> Result<()> {
-    let notifier = NotificationManager::new();
+async fn run_sync_service(
+    clipboard: ClipboardManager,
+    network: NetworkManager,
+    source: &ConfigSource,
+    config: Config,
+    qr: &QrArgs,
+) -> Result<()> {
+    let notifier = NotificationManager::new(&config.notifications);
 
-    println!("启动剪贴板同步服务...");
+    info!("启动剪贴板同步服务...");
 
     // 发送启动通知
     notifier.send("剪贴板同步", "同步服务已启动")?;
 
     // 显示连接信息
-    let ticket = network.generate_ticket().await?;
+    let ticket = network
+        .generate_ticket(ticket::DEFAULT_TICKET_TTL, false)
+        .await?;
     println!("节点 ID: {}", network.get_node_id());
     println!("连接票据: {}", ticket);
+    println!("(票据 {} 分钟内有效，可运行 clipboard-sync ticket 重新生成)", ticket::DEFAULT_TICKET_TTL.as_secs() / 60);
     println!("\n其他设备可以使用以下命令连接到此设备:");
     println!("clipboard-sync -- connect {}", ticket);
-    println!("");
+    show_ticket_qr(&ticket, qr)?;
+    println!();
     println!("正在监听连接和剪贴板变化...");
     println!("按 Ctrl+C 停止服务");
 
     // 设置消息处理器
-    let mut message_receiver = network.setup_message_handler().await;
+    let message_receiver = network.setup_message_handler().await;
 
-    // 启动网络监听任务
-    // let network_clone = network.clone();
-    // tokio::spawn(async move {
-    //     if let Err(e) = network_clone.listen_for_connections().await {
-    //         eprintln!("网络监听失败: {}", e);
-    //     }
-    // });
+    // 启动控制通道和配置文件监视
+    let reloader = spawn_control(&network, &clipboard, &notifier, source, config);
 
     // 启动消息处理任务
-    let clipboard_clone = clipboard.clone();
-    let notifier_clone = notifier.clone();
-    tokio::spawn(async move {
-        while let Some(message) = message_receiver.recv().await {
-            println!(
-                "收到剪贴板消息: {} (来自: {})",
-                message.content, message.sender_id
-            );
+    spawn_message_receiver(clipboard.clone(), notifier.clone(), reloader.clone(), message_receiver);
 
-            // 根据消息类型更新本地剪贴板
-            match &message.content {
-                network::ClipboardContent::Text(text) => {
-                    if let Err(e) = clipboard_clone.set_text(text) {
-                        eprintln!("更新文本剪贴板失败: {}", e);
-                    } else {
-                        let preview = message.content.preview(50);
-                        let _ = notifier_clone.send("文本剪贴板已同步", &preview);
-                    }
-                }
-                network::ClipboardContent::Image {
-                    width,
-                    height,
-                    data,
-                } => {
-                    if let Err(e) = clipboard_clone.set_image(*width, *height, data) {
-                        eprintln!("更新图片剪贴板失败: {}", e);
-                    } else {
-                        let preview = format!("图片 {}x{}", width, height);
-                        let _ = notifier_clone.send("图片剪贴板已同步", &preview);
-                    }
-                }
-            }
-        }
-    });
-
-    // 剪贴板监控循环
-    let mut last_text_content = String::new();
-    let mut last_content_type = clipboard::ClipboardContentType::Empty;
-
-    loop {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-
-        // 检查剪贴板内容类型
-        let current_type = clipboard.get_content_type();
-
-        match current_type {
-            clipboard::ClipboardContentType::Text => {
-                if let Ok(current_content) = clipboard.get_text() {
-                    if current_content != last_text_content && !current_content.is_empty() {
-                        println!("检测到文本剪贴板变化: {}", current_content);
-
-                        // 广播文本到其他设备
-                        if let Err(e) = network.broadcast_clipboard(&current_content).await {
-                            eprintln!("文本广播失败: {}", e);
-                        }
-
-                        last_text_content = current_content;
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Image => {
-                // 只有当之前不是图片类型时才处理，避免重复处理
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Image) {
-                    if let Ok(Some((width, height, png_data))) = clipboard.get_image() {
-                        println!("检测到图片剪贴板变化: {}x{}", width, height);
-
-                        // 广播图片到其他设备
-                        if let Err(e) = network.broadcast_image(width, height, png_data).await {
-                            eprintln!("图片广播失败: {}", e);
-                        }
-
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Empty => {
-                // 剪贴板为空，更新状态
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Empty) {
-                    last_content_type = current_type;
-                    last_text_content.clear();
-                }
-            }
-        }
-
-        // 检查退出信号
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                break;
-            }
-            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
-        }
-    }
+    // 剪贴板监控循环，直到按下 Ctrl+C
+    monitor_clipboard(&clipboard, &network, &reloader).await;
 
     network.shutdown().await;
-    println!("同步服务已停止");
+    info!("同步服务已停止");
 
     Ok(())
 }
@@ -361,127 +1264,35 @@ async fn run_sync_service(clipboard: ClipboardManager, network: NetworkManager)
 async fn connect_to_peer(
     clipboard: ClipboardManager,
     network: NetworkManager,
+    source: &ConfigSource,
+    config: Config,
     ticket: &str,
 ) -> Result<()> {
-    let notifier = NotificationManager::new();
+    let notifier = NotificationManager::new(&config.notifications);
 
-    println!("正在连接到其他设备...");
+    info!("正在连接到其他设备...");
 
     network.connect_to_peer(ticket).await?;
 
-    println!("连接成功！开始同步剪贴板内容...");
+    info!("连接成功！开始同步剪贴板内容...");
     notifier.send("剪贴板同步", "已连接到其他设备")?;
 
     println!("按 Ctrl+C 断开连接");
 
     // 设置消息处理器
-    let mut message_receiver = network.setup_message_handler().await;
+    let message_receiver = network.setup_message_handler().await;
 
-    // 启动网络监听任务
-    // let network_clone = network.clone();
-    // tokio::spawn(async move {
-    //     if let Err(e) = network_clone.listen_for_connections().await {
-    //         eprintln!("网络监听失败: {}", e);
-    //     }
-    // });
+    // 启动控制通道和配置文件监视
+    let reloader = spawn_control(&network, &clipboard, &notifier, source, config);
 
     // 启动消息处理任务
-    let clipboard_clone = clipboard.clone();
-    let notifier_clone = notifier.clone();
-    tokio::spawn(async move {
-        while let Some(message) = message_receiver.recv().await {
-            println!(
-                "收到剪贴板消息: {} (来自: {})",
-                message.content, message.sender_id
-            );
-
-            // 根据消息类型更新本地剪贴板
-            match &message.content {
-                network::ClipboardContent::Text(text) => {
-                    if let Err(e) = clipboard_clone.set_text(text) {
-                        eprintln!("更新文本剪贴板失败: {}", e);
-                    } else {
-                        let preview = message.content.preview(50);
-                        let _ = notifier_clone.send("文本剪贴板已同步", &preview);
-                    }
-                }
-                network::ClipboardContent::Image {
-                    width,
-                    height,
-                    data,
-                } => {
-                    if let Err(e) = clipboard_clone.set_image(*width, *height, data) {
-                        eprintln!("更新图片剪贴板失败: {}", e);
-                    } else {
-                        let preview = format!("图片 {}x{}", width, height);
-                        let _ = notifier_clone.send("图片剪贴板已同步", &preview);
-                    }
-                }
-            }
-        }
-    });
+    spawn_message_receiver(clipboard.clone(), notifier.clone(), reloader.clone(), message_receiver);
 
-    // 剪贴板监控循环
-    let mut last_text_content = String::new();
-    let mut last_content_type = clipboard::ClipboardContentType::Empty;
-
-    loop {
-        tokio::time::sleep(Duration::from_millis(500)).await;
-
-        // 检查剪贴板内容类型
-        let current_type = clipboard.get_content_type();
-
-        match current_type {
-            clipboard::ClipboardContentType::Text => {
-                if let Ok(current_content) = clipboard.get_text() {
-                    if current_content != last_text_content && !current_content.is_empty() {
-                        println!("检测到文本剪贴板变化: {}", current_content);
-
-                        // 广播文本到其他设备
-                        if let Err(e) = network.broadcast_clipboard(&current_content).await {
-                            eprintln!("文本广播失败: {}", e);
-                        }
-
-                        last_text_content = current_content;
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Image => {
-                // 只有当之前不是图片类型时才处理，避免重复处理
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Image) {
-                    if let Ok(Some((width, height, png_data))) = clipboard.get_image() {
-                        println!("检测到图片剪贴板变化: {}x{}", width, height);
-
-                        // 广播图片到其他设备
-                        if let Err(e) = network.broadcast_image(width, height, png_data).await {
-                            eprintln!("图片广播失败: {}", e);
-                        }
-
-                        last_content_type = current_type;
-                    }
-                }
-            }
-            clipboard::ClipboardContentType::Empty => {
-                // 剪贴板为空，更新状态
-                if !matches!(last_content_type, clipboard::ClipboardContentType::Empty) {
-                    last_content_type = current_type;
-                    last_text_content.clear();
-                }
-            }
-        }
-
-        // 检查退出信号
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                break;
-            }
-            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
-        }
-    }
+    // 剪贴板监控循环，直到按下 Ctrl+C
+    monitor_clipboard(&clipboard, &network, &reloader).await;
 
     network.shutdown().await;
-    println!("连接已断开");
+    info!("连接已断开");
 
     Ok(())
 }
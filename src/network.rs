@@ -1,86 +1,881 @@
 use anyhow::Result;
 use iroh::protocol::{ProtocolHandler, Router, AcceptError};
-use iroh::{Endpoint, NodeAddr, NodeId};
+use iroh::{Endpoint, NodeAddr, NodeId, SecretKey, Watcher};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use std::future::Future;
 use tokio::sync::{mpsc, Mutex};
-use tokio::io::AsyncReadExt;
 use futures_lite::StreamExt;
+use tracing::{debug, error, info, warn};
+
+use crate::announce::ServiceAnnouncement;
+use crate::clipboard::Selection;
+use crate::config::{ContentFilter, SizeLimits};
+use crate::dedup::SeenMessages;
+use crate::gossip::{self, GossipTransport};
+use crate::groups::{GroupStore, SyncGroup};
+use crate::history::{History, HistorySource, DEFAULT_HISTORY_CAPACITY};
+use crate::logging;
+use crate::sensitive::{self, SensitivePolicy};
+use crate::snippets::{Snippet, SnippetStore};
+use crate::pause::{PauseDirection, SyncControl};
+use crate::pairing::{IssuedCode, PairingCode, PairingHandshake, PairingKey};
+use crate::storage;
+use crate::ticket::{ConnectionTicket, UsedTickets};
+use crate::trust::{DiscoveredDevice, PeerPolicy, TrustStore, TrustedDevice};
 
 // 定义我们的协议ALPN
 const CLIPBOARD_ALPN: &[u8] = b"iroh-clipboard-sync/0";
 
+/// 单条消息的最大字节数
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// 默认的最大转发跳数
+pub const DEFAULT_MAX_HOPS: u8 = 3;
+
+/// 使用配对码时在局域网中寻找签发设备的最长时间
+const PAIRING_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 单次发送时等待对方读取消息的最长时间
+const SEND_CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 消息签名内容的域分隔前缀
+const MESSAGE_SIGNING_CONTEXT: &[u8] = b"clipboard-sync message v1:";
+
+type ConnectionMap = Arc<Mutex<HashMap<NodeId, iroh::endpoint::Connection>>>;
+
+/// 传输模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportMode {
+    /// 与每个设备直接建立连接（全连接广播）
+    #[default]
+    Direct,
+    /// 通过 iroh-gossip 主题同步，主题由组密钥派生
+    Gossip,
+}
+
+/// 网络选项
+#[derive(Debug, Clone)]
+pub struct NetworkOptions {
+    /// 是否将收到的消息转发给其他已连接的设备（多跳中继）
+    pub relay: bool,
+    /// 消息最多被转发的跳数
+    pub max_hops: u8,
+    /// 传输模式
+    pub transport: TransportMode,
+    /// gossip 模式下共享的组密钥
+    pub group_secret: Option<String>,
+    /// 本机参与同步的命名组，为空时不区分组
+    pub groups: Vec<SyncGroup>,
+    /// 启动时即进入隐身模式
+    pub incognito: bool,
+    /// 是否启用局域网设备发现
+    pub local_discovery: bool,
+    /// 同步规则，服务运行时可以重新加载
+    pub rules: SyncRules,
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self {
+            relay: false,
+            max_hops: DEFAULT_MAX_HOPS,
+            transport: TransportMode::Direct,
+            group_secret: None,
+            groups: Vec::new(),
+            incognito: false,
+            local_discovery: true,
+            rules: SyncRules::default(),
+        }
+    }
+}
+
+/// 决定哪些内容可以同步的规则，修改后无需重新建立连接
+#[derive(Debug, Clone, Default)]
+pub struct SyncRules {
+    /// 配置文件中声明的可信设备，无需批准
+    pub trusted_peers: Vec<NodeId>,
+    /// 同步内容的大小上限
+    pub limits: SizeLimits,
+    /// 本机内容的同步过滤规则
+    pub filters: ContentFilter,
+    /// 复制了疑似敏感内容时的处理策略
+    pub sensitive_policy: SensitivePolicy,
+    /// 接收方在多久后清除同步过去的内容
+    pub clear_after: Option<Duration>,
+    /// 同步疑似敏感内容时使用的清除时间，未设置 clear_after 时生效
+    pub sensitive_clear_after: Option<Duration>,
+}
+
+/// 主动连接时向对方出示的凭据
+#[derive(Debug, Clone)]
+pub enum Credential {
+    /// 对方签发的连接票据
+    Ticket(String),
+    /// 对方签发的配对码
+    PairingCode(PairingCode),
+}
+
 /// 剪贴板协议处理器
 #[derive(Debug, Clone)]
 pub struct ClipboardProtocol {
     message_sender: Arc<Mutex<Option<mpsc::UnboundedSender<ClipboardMessage>>>>,
+    seen_messages: Arc<Mutex<SeenMessages>>,
+    connections: ConnectionMap,
+    peer_groups: Arc<Mutex<HashMap<NodeId, HashSet<String>>>>,
+    group_members: Arc<Mutex<HashMap<String, HashSet<String>>>>, // 已持久化的组成员
+    trust: Arc<Mutex<TrustStore>>,
+    used_tickets: Arc<Mutex<UsedTickets>>,
+    discovered: Arc<Mutex<Vec<DiscoveredDevice>>>,
+    revoked: Arc<Mutex<HashSet<NodeId>>>, // 本次运行中撤销的设备，其消息一律丢弃
+    pairing: Arc<Mutex<Option<IssuedCode>>>,
+    sync_control: Arc<Mutex<SyncControl>>,
+    rules: Arc<Mutex<SyncRules>>,
+    history: History,
+    snippets: Arc<Mutex<SnippetStore>>,
+    options: NetworkOptions,
+    node_id: NodeId,
+    hello: PeerHello,
 }
 
 impl ClipboardProtocol {
-    pub fn new() -> Self {
+    pub fn new(
+        connections: ConnectionMap,
+        options: NetworkOptions,
+        trust: TrustStore,
+        used_tickets: UsedTickets,
+        snippets: SnippetStore,
+        node_id: NodeId,
+        device_name: String,
+    ) -> Self {
+        let hello = PeerHello {
+            device_name,
+            ticket: None,
+            pairing: None,
+            groups: options
+                .groups
+                .iter()
+                .map(|group| GroupProof {
+                    tag: group.discovery_tag(),
+                    proof: group.membership_proof(&node_id),
+                })
+                .collect(),
+        };
+
+        let group_members = options
+            .groups
+            .iter()
+            .map(|group| (group.name.clone(), group.members.iter().cloned().collect()))
+            .collect();
+
+        let mut sync_control = SyncControl::default();
+        sync_control.set_incognito(options.incognito);
+
         Self {
             message_sender: Arc::new(Mutex::new(None)),
+            seen_messages: Arc::new(Mutex::new(SeenMessages::default())),
+            connections,
+            peer_groups: Arc::new(Mutex::new(HashMap::new())),
+            group_members: Arc::new(Mutex::new(group_members)),
+            trust: Arc::new(Mutex::new(trust)),
+            used_tickets: Arc::new(Mutex::new(used_tickets)),
+            discovered: Arc::new(Mutex::new(Vec::new())),
+            revoked: Arc::new(Mutex::new(HashSet::new())),
+            pairing: Arc::new(Mutex::new(None)),
+            sync_control: Arc::new(Mutex::new(sync_control)),
+            rules: Arc::new(Mutex::new(options.rules.clone())),
+            history: History::new(DEFAULT_HISTORY_CAPACITY),
+            snippets: Arc::new(Mutex::new(snippets)),
+            options,
+            node_id,
+            hello,
         }
     }
+
+    /// 记录消息 ID，若是首次出现则返回 true
+    pub async fn mark_seen(&self, message: &ClipboardMessage) -> bool {
+        self.seen_messages.lock().await.insert(&message.id)
+    }
     
     pub async fn set_message_sender(&self, sender: mpsc::UnboundedSender<ClipboardMessage>) {
         *self.message_sender.lock().await = Some(sender);
     }
-}
 
-impl ProtocolHandler for ClipboardProtocol {
-    fn accept(&self, connection: iroh::endpoint::Connection) -> impl Future<Output = Result<(), AcceptError>> + Send {
-        let message_sender = self.message_sender.clone();
-        
-        async move {
-            println!("接受剪贴板协议连接");
-            
-            // 接受双向流
-            let result = connection.accept_bi().await;
-            let (_send_stream, mut recv_stream) = match result {
-                Ok(streams) => streams,
+    /// 检查设备是否已被批准
+    pub async fn is_trusted(&self, node_id: &NodeId) -> bool {
+        if self.revoked.lock().await.contains(node_id) {
+            return false;
+        }
+        self.rules.lock().await.trusted_peers.contains(node_id) || self.trust.lock().await.is_trusted(node_id)
+    }
+
+    /// 替换同步规则和可信设备列表，之后的消息按新的同步策略处理
+    ///
+    /// 已连接但不再受信任的设备立即断开，返回断开的设备数。
+    pub async fn update_rules(&self, rules: SyncRules, trust: TrustStore) -> usize {
+        *self.rules.lock().await = rules;
+        *self.trust.lock().await = trust;
+
+        let connected: Vec<NodeId> = self.connections.lock().await.keys().copied().collect();
+        let mut disconnected = 0;
+        for node_id in connected {
+            if self.is_trusted(&node_id).await {
+                continue;
+            }
+            self.peer_groups.lock().await.remove(&node_id);
+            if let Some(connection) = self.connections.lock().await.remove(&node_id) {
+                connection.close(0u32.into(), b"not trusted");
+                info!("设备 {} 已不在可信列表中，已断开连接", node_id);
+                disconnected += 1;
+            }
+        }
+        disconnected
+    }
+
+    /// 将未批准的设备加入发现列表，若是新设备则提示用户批准
+    ///
+    /// 列表只增不减，设备的编号在本次运行中保持不变，批准其他设备不会让编号错位。
+    pub async fn record_discovered(&self, node_id: NodeId, device_name: &str) {
+        let mut discovered = self.discovered.lock().await;
+        if discovered.iter().any(|device| device.node_id == node_id) {
+            return;
+        }
+
+        let number = discovered.len() + 1;
+        discovered.push(DiscoveredDevice {
+            number,
+            node_id,
+            device_name: device_name.to_string(),
+            first_seen: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        });
+
+        info!("🆕 发现新设备 [{}] {} ({})", number, device_name, node_id);
+        info!("   输入 `approve {}` 或运行 `clipboard-sync devices approve {}` 批准同步", number, node_id);
+    }
+
+    /// 持续接收对方发来的消息，直到连接关闭
+    ///
+    /// 主动发起和被动接受的连接都走这里，这样双方都能通过同一条连接收发消息。
+    /// 主动连接时可以附带票据或配对码，对方据此把本机加入可信设备。
+    /// 无论以哪种模式运行，未被信任的设备都必须出示本机签发的有效票据，
+    /// 或用本机签发的配对码完成握手，否则连接会被拒绝。兑换过票据或完成过配对的设备
+    /// 已加入可信设备，之后再连接时无需重复出示。
+    pub async fn handle_connection(&self, connection: iroh::endpoint::Connection, credential: Option<Credential>) {
+        let node_id = match connection.remote_node_id() {
+            Ok(node_id) => node_id,
+            Err(e) => {
+                warn!("无法获取对方节点 ID: {}", e);
+                return;
+            }
+        };
+
+        let mut authorized = self.is_trusted(&node_id).await;
+
+        // 先发送问候，告知对方本机所属的组
+        let mut hello = self.hello.clone();
+        let (code, issued_here) = match credential {
+            Some(Credential::Ticket(ticket)) => {
+                hello.ticket = Some(ticket);
+                (None, false)
+            }
+            Some(Credential::PairingCode(code)) => (Some(code), false),
+            // 被动接受的连接使用本机当前签发的配对码
+            None => (self.active_pairing_code().await, true),
+        };
+        // 出示或签发了配对码时总是握手，不论本机是否已信任对方，否则对方收不到握手消息会拒绝连接
+        let mut handshake = None;
+        if let Some(code) = code {
+            let (state, message) = PairingHandshake::start(&code);
+            hello.pairing = Some(message);
+            handshake = Some(state);
+        }
+        let mut pending_pairing: Option<(PeerHello, PairingKey)> = None;
+
+        match WireMessage::Hello(hello).to_bytes() {
+            Ok(data) => {
+                if let Err(e) = send_frame(&connection, &data).await {
+                    warn!("发送问候到 {} 失败: {}", node_id, e);
+                }
+            }
+            Err(e) => {
+                warn!("问候消息序列化失败: {}", e);
+            }
+        }
+
+        if authorized {
+            self.connections.lock().await.insert(node_id, connection.clone());
+        }
+
+        // 每条消息使用一个独立的双向流，对方的问候总是第一帧
+        while let Ok((_send_stream, mut recv_stream)) = connection.accept_bi().await {
+            let data = match recv_stream.read_to_end(MAX_MESSAGE_SIZE).await {
+                Ok(data) => data,
                 Err(e) => {
-                    eprintln!("Failed to accept bidirectional stream: {}", e);
-                    return Err(AcceptError::from(e));
+                    warn!("读取来自 {} 的消息失败: {}", node_id, e);
+                    continue;
                 }
             };
-            
-            // 读取消息
-            let mut buf = [0u8; 4096];
-            while let Ok(Some(n)) = recv_stream.read(&mut buf).await {
-                if n == 0 {
-                    break;
-                }
-                
-                match ClipboardMessage::from_bytes(&buf[..n]) {
-                    Ok(message) => {
-                        match &message.content {
-                            ClipboardContent::Text(text) => {
-                                println!("收到文本消息: {} (来自: {})", text, message.sender_id);
-                            }
-                            ClipboardContent::Image { width, height, .. } => {
-                                println!("收到图片消息: {}x{} (来自: {})", width, height, message.sender_id);
+
+            match WireMessage::from_bytes(&data) {
+                Ok(WireMessage::Hello(hello)) => {
+                    // 对方带着配对码握手消息时，先完成握手，等待双方确认
+                    if let (Some(state), Some(message)) = (handshake.take(), hello.pairing.as_deref()) {
+                        match self.answer_pairing(&connection, state, message, issued_here).await {
+                            Ok(key) => pending_pairing = Some((hello, key)),
+                            Err(e) => {
+                                warn!("与设备 {} 配对失败: {}", hello.device_name, e);
+                                connection.close(0u32.into(), b"pairing failed");
+                                break;
                             }
                         }
-                        
-                        if let Some(sender) = message_sender.lock().await.as_ref() {
-                            let _ = sender.send(message);
+                        continue;
+                    }
+                    if !authorized {
+                        if !self.redeem_ticket(&hello, node_id).await {
+                            info!("拒绝未批准设备的连接: {} ({})", hello.device_name, node_id);
+                            self.record_discovered(node_id, &hello.device_name).await;
+                            connection.close(0u32.into(), b"not approved");
+                            break;
+                        }
+                        authorized = true;
+                        self.connections.lock().await.insert(node_id, connection.clone());
+                    }
+                    self.handle_hello(hello, node_id).await;
+                }
+                Ok(WireMessage::PairingConfirm(confirmation)) => {
+                    let Some((hello, key)) = pending_pairing.take() else {
+                        continue;
+                    };
+                    if !key.verify_confirmation(&node_id, &confirmation) {
+                        warn!("配对码不一致，拒绝设备 {} ({})", hello.device_name, node_id);
+                        connection.close(0u32.into(), b"pairing failed");
+                        break;
+                    }
+                    if let Err(e) = self.trust.lock().await.approve(&node_id, &hello.device_name) {
+                        error!("保存可信设备失败: {}", e);
+                    }
+                    info!("✅ 设备 {} 通过配对码加入同步", hello.device_name);
+                    if !authorized {
+                        authorized = true;
+                        self.connections.lock().await.insert(node_id, connection.clone());
+                    }
+                    self.handle_hello(hello, node_id).await;
+                }
+                Ok(WireMessage::Clipboard(message)) => {
+                    if authorized {
+                        self.handle_message(message, node_id).await;
+                    }
+                }
+                Ok(WireMessage::Snippets(snippets)) => {
+                    if authorized {
+                        self.handle_snippets(snippets, node_id).await;
+                    }
+                }
+                Err(e) => {
+                    warn!("消息解析失败: {}", e);
+                }
+            }
+        }
+
+        // 连接已关闭，只移除仍是这条连接的记录
+        let mut connections = self.connections.lock().await;
+        if connections
+            .get(&node_id)
+            .is_some_and(|c| c.stable_id() == connection.stable_id())
+        {
+            connections.remove(&node_id);
+            self.peer_groups.lock().await.remove(&node_id);
+        }
+    }
+
+    /// 本机当前签发且未过期的配对码
+    async fn active_pairing_code(&self) -> Option<PairingCode> {
+        self.pairing
+            .lock()
+            .await
+            .as_ref()
+            .filter(|issued| !issued.is_expired())
+            .map(|issued| issued.code.clone())
+    }
+
+    /// 完成配对握手并发送本机的确认码
+    ///
+    /// 本机签发的配对码只允许尝试一次，无论成功与否都会作废，防止被逐个猜测。
+    async fn answer_pairing(
+        &self,
+        connection: &iroh::endpoint::Connection,
+        state: PairingHandshake,
+        message: &str,
+        issued_here: bool,
+    ) -> Result<PairingKey> {
+        if issued_here && self.pairing.lock().await.take().is_none() {
+            anyhow::bail!("配对码已被使用或已过期");
+        }
+
+        let key = state.finish(message)?;
+        let confirmation = WireMessage::PairingConfirm(key.confirmation(&self.node_id)).to_bytes()?;
+        send_frame(connection, &confirmation).await?;
+        Ok(key)
+    }
+
+    /// 兑换对方出示的票据，成功时把对方加入可信设备
+    async fn redeem_ticket(&self, hello: &PeerHello, from: NodeId) -> bool {
+        let Some(ticket_str) = &hello.ticket else {
+            return false;
+        };
+
+        let ticket = match ConnectionTicket::from_string(ticket_str) {
+            Ok(ticket) => ticket,
+            Err(e) => {
+                warn!("设备 {} 出示的票据无效: {}", from, e);
+                return false;
+            }
+        };
+        if ticket.node_id != self.node_id {
+            warn!("设备 {} 出示的票据不是本机签发的", from);
+            return false;
+        }
+        if let Err(e) = ticket.verify() {
+            warn!("设备 {} 出示的票据无效: {}", from, e);
+            return false;
+        }
+        if ticket.single_use {
+            match self.used_tickets.lock().await.mark_used(&ticket) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!("设备 {} 出示的一次性票据已被使用", from);
+                    return false;
+                }
+                Err(e) => {
+                    error!("记录一次性票据失败: {}", e);
+                    return false;
+                }
+            }
+        }
+
+        if let Err(e) = self.trust.lock().await.approve(&from, &hello.device_name) {
+            error!("保存可信设备失败: {}", e);
+        }
+        info!("✅ 设备 {} 凭票据加入同步", hello.device_name);
+        true
+    }
+
+    /// 处理对方的问候，记录其证明了成员身份的组
+    async fn handle_hello(&self, hello: PeerHello, from: NodeId) {
+        // 问候中只出示组的发现标识，不暴露组名
+        let verified: HashSet<String> = self
+            .options
+            .groups
+            .iter()
+            .filter(|group| {
+                let tag = group.discovery_tag();
+                hello
+                    .groups
+                    .iter()
+                    .any(|claim| claim.tag == tag && group.verify_membership(&from, &claim.proof))
+            })
+            .map(|group| group.name.clone())
+            .collect();
+
+        if !verified.is_empty() {
+            let names: Vec<&str> = verified.iter().map(String::as_str).collect();
+            info!("设备 {} 属于同步组: {}", hello.device_name, names.join(", "));
+
+            // 只有出现新成员时才持久化
+            let new_groups: Vec<String> = {
+                let mut members = self.group_members.lock().await;
+                let from = from.to_string();
+                verified
+                    .iter()
+                    .filter(|name| members.entry((*name).clone()).or_default().insert(from.clone()))
+                    .cloned()
+                    .collect()
+            };
+            if !new_groups.is_empty() {
+                match GroupStore::load() {
+                    Ok(mut store) => {
+                        for name in &new_groups {
+                            if let Err(e) = store.add_member(name, &from) {
+                                error!("保存组成员失败: {}", e);
+                            }
                         }
                     }
                     Err(e) => {
-                        eprintln!("消息解析失败: {}", e);
+                        error!("加载同步组失败: {}", e);
                     }
                 }
             }
-            
+        }
+
+        self.peer_groups.lock().await.insert(from, verified);
+
+        // 连接建立后交换全部片段，离线期间的修改也能同步过来
+        let snippets = self.snippets.lock().await.all().to_vec();
+        self.push_snippets(&snippets, |node_id| node_id == from).await;
+    }
+
+    /// 合并其他设备发来的片段，有变化时保存并按需转发
+    async fn handle_snippets(&self, snippets: Vec<Snippet>, from: NodeId) {
+        if !self.shares_snippets(self.peer_groups.lock().await.get(&from)) {
+            warn!("设备 {} 未完成问候或不在本机的同步组中，忽略发来的片段", from);
+            return;
+        }
+        let policy = self.trust.lock().await.policy(&from);
+        let limits = self.rules.lock().await.limits.clone();
+
+        let mut changed = Vec::new();
+        {
+            let mut store = self.snippets.lock().await;
+            for snippet in snippets {
+                if snippet
+                    .content
+                    .as_ref()
+                    .is_some_and(|content| !policy.allows_receive(content) || !limits.allows(content))
+                {
+                    continue;
+                }
+                if store.merge(snippet.clone()) {
+                    changed.push(snippet);
+                }
+            }
+            if changed.is_empty() {
+                return;
+            }
+            if let Err(e) = store.save() {
+                error!("保存片段失败: {}", e);
+            }
+        }
+        info!("已从其他设备同步 {} 个片段", changed.len());
+
+        if self.options.relay {
+            self.push_snippets(&changed, |node_id| node_id != from).await;
+        }
+    }
+
+    /// 将片段发给满足条件的已连接设备，同步策略不允许发送的内容会被跳过
+    async fn push_snippets(&self, snippets: &[Snippet], select: impl Fn(NodeId) -> bool) {
+        let peer_groups = self.peer_groups.lock().await;
+        let targets: Vec<(NodeId, iroh::endpoint::Connection)> = self
+            .connections
+            .lock()
+            .await
+            .iter()
+            .filter(|(node_id, _)| select(**node_id))
+            .filter(|(node_id, _)| self.shares_snippets(peer_groups.get(*node_id)))
+            .map(|(node_id, connection)| (*node_id, connection.clone()))
+            .collect();
+        drop(peer_groups);
+
+        for (node_id, connection) in targets {
+            let policy = self.trust.lock().await.policy(&node_id);
+            let allowed: Vec<Snippet> = snippets
+                .iter()
+                .filter(|snippet| snippet.content.as_ref().is_none_or(|content| policy.allows_send(content)))
+                .cloned()
+                .collect();
+            if allowed.is_empty() {
+                continue;
+            }
+            let result = match WireMessage::Snippets(allowed).to_bytes() {
+                Ok(data) => send_frame(&connection, &data).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                warn!("发送片段到 {} 失败: {}", node_id, e);
+            }
+        }
+    }
+
+    /// 是否与对方同步片段，`groups` 是对方问候时验证过的组，未问候时为 None
+    ///
+    /// 片段不区分组，本机加入了同步组时只和至少共享一个组的设备交换。
+    fn shares_snippets(&self, groups: Option<&HashSet<String>>) -> bool {
+        groups.is_some_and(|groups| self.options.groups.is_empty() || !groups.is_empty())
+    }
+
+    /// 检查消息是否属于本机所在的组
+    ///
+    /// 未加入任何组时只接受未标记组的消息；否则只接受本机所在组且组标签有效的消息。
+    fn accepts_group(&self, message: &ClipboardMessage) -> bool {
+        match &message.group {
+            None => self.options.groups.is_empty(),
+            Some(name) => self
+                .options
+                .groups
+                .iter()
+                .find(|group| &group.name == name)
+                .is_some_and(|group| {
+                    message.group_tag.as_deref().is_some_and(|tag| {
+                        group.verify_message_tag(&message.id, &message.content_hash, tag)
+                    })
+                }),
+        }
+    }
+
+    /// 校验并去重消息，若是新消息则交给上层，返回是否已交付
+    ///
+    /// 消息必须带有有效签名，按发出设备的同步策略过滤；`from` 是把消息交给本机的设备，
+    /// 它或发出设备已被撤销时丢弃消息。
+    pub async fn deliver(&self, mut message: ClipboardMessage, from: Option<NodeId>) -> bool {
+        if !message.verify_hash() {
+            warn!("消息内容校验失败，已丢弃 (来自: {})", message.sender_id);
+            return false;
+        }
+
+        // 清除时间、隐身标记等字段决定接收方如何处理内容，必须确认出自发出节点
+        let Some(origin) = message.verified_origin() else {
+            warn!("消息签名无效，已丢弃 (来自: {})", message.sender_id);
+            return false;
+        };
+
+        // 不属于本机所在组的消息直接丢弃
+        if !self.accepts_group(&message) {
+            return false;
+        }
+
+        if !self.rules.lock().await.limits.allows(&message.content) {
+            debug!("收到的内容超过大小限制，已丢弃 (来自: {})", message.sender_id);
+            return false;
+        }
+
+        // 已撤销设备发出或转发的消息一律丢弃
+        {
+            let revoked = self.revoked.lock().await;
+            if revoked.contains(&origin) || from.is_some_and(|from| revoked.contains(&from)) {
+                debug!("消息来自已撤销的设备，已丢弃 (来自: {})", message.sender_id);
+                return false;
+            }
+        }
+
+        // 按发出消息的设备的策略判断，经由其他设备转发的消息也不能绕过
+        if !self.trust.lock().await.policy(&origin).allows_receive(&message.content) {
+            debug!("设备 {} 的同步策略不接受该内容，已丢弃", origin);
+            return false;
+        }
+
+        // 重复投递的消息直接丢弃
+        if !self.mark_seen(&message).await {
+            return false;
+        }
+
+        // 发送方未标记时本机再检测一次，避免在日志中输出敏感内容
+        if let ClipboardContent::Text(text) = &message.content {
+            message.sensitive |= sensitive::detect(text).is_some();
+        }
+
+        let (inbound_paused, incognito) = {
+            let control = self.sync_control.lock().await;
+            (control.inbound_paused(), control.is_incognito())
+        };
+        message.incognito |= incognito;
+        if !message.incognito {
+            info!(from = %message.sender_id, content = %message.log_summary(), "收到剪贴板消息");
+        }
+
+        // 暂停接收时不应用到本机剪贴板，但仍照常转发
+        if inbound_paused {
+            debug!("接收已暂停，忽略收到的内容");
+            return true;
+        }
+
+        self.history
+            .record(&message, HistorySource::Remote(message.sender_id.clone()))
+            .await;
+        if let Some(sender) = self.message_sender.lock().await.as_ref() {
+            let _ = sender.send(message);
+        }
+        true
+    }
+
+    /// 处理直连收到的消息：交给上层并按需转发
+    async fn handle_message(&self, message: ClipboardMessage, from: NodeId) {
+        if !self.deliver(message.clone(), Some(from)).await {
+            return;
+        }
+
+        if self.options.relay && message.hops < self.options.max_hops {
+            let mut forwarded = message;
+            forwarded.hops += 1;
+            if let Err(e) = self.send_message(&forwarded, Some(from)).await {
+                warn!("转发消息失败: {}", e);
+            }
+        }
+    }
+
+    /// 将消息发送给消息所属组的已连接成员（可排除某个节点）
+    pub async fn send_message(&self, message: &ClipboardMessage, exclude: Option<NodeId>) -> Result<()> {
+        self.send_to(message, |node_id| Some(node_id) != exclude, false)
+            .await
+            .map(|_| ())
+    }
+
+    /// 将消息发送给满足条件的已连接组成员，返回发送成功的设备
+    ///
+    /// 同步策略不允许发送这类内容的设备会被跳过。`confirm` 为 true 时等待每台设备读取完毕。
+    async fn send_to(
+        &self,
+        message: &ClipboardMessage,
+        select: impl Fn(NodeId) -> bool,
+        confirm: bool,
+    ) -> Result<Vec<NodeId>> {
+        let data = WireMessage::Clipboard(message.clone()).to_bytes()?;
+
+        let trust = self.trust.lock().await;
+        let peer_groups = self.peer_groups.lock().await;
+        let targets: Vec<_> = self
+            .connections
+            .lock()
+            .await
+            .iter()
+            .filter(|(node_id, _)| select(**node_id))
+            .filter(|(node_id, _)| match &message.group {
+                None => true,
+                Some(name) => peer_groups
+                    .get(*node_id)
+                    .is_some_and(|groups| groups.contains(name)),
+            })
+            .filter(|(node_id, _)| trust.policy(node_id).allows_send(&message.content))
+            .map(|(node_id, connection)| (*node_id, connection.clone()))
+            .collect();
+        drop(peer_groups);
+        drop(trust);
+
+        let mut failed_connections = Vec::new();
+        let mut delivered = Vec::new();
+
+        for (node_id, connection) in targets {
+            let result = if confirm {
+                send_frame_confirmed(&connection, &data).await
+            } else {
+                send_frame(&connection, &data).await
+            };
+            match result {
+                Ok(()) => {
+                    debug!("消息已发送到: {}", node_id);
+                    delivered.push(node_id);
+                }
+                Err(e) => {
+                    warn!("发送到 {} 失败: {}", node_id, e);
+                    failed_connections.push(node_id);
+                }
+            }
+        }
+
+        // 清理失败的连接
+        if !failed_connections.is_empty() {
+            let mut connections = self.connections.lock().await;
+            for node_id in failed_connections {
+                connections.remove(&node_id);
+            }
+        }
+
+        Ok(delivered)
+    }
+}
+
+impl ProtocolHandler for ClipboardProtocol {
+    fn accept(&self, connection: iroh::endpoint::Connection) -> impl Future<Output = Result<(), AcceptError>> + Send {
+        let protocol = self.clone();
+        
+        async move {
+            debug!("接受剪贴板协议连接");
+            protocol.handle_connection(connection, None).await;
             Ok(())
         }
     }
 }
 
+/// 在新的双向流上发送一帧数据
+async fn send_frame(connection: &iroh::endpoint::Connection, data: &[u8]) -> Result<()> {
+    let (mut send_stream, _recv_stream) = connection.open_bi().await?;
+    send_stream.write_all(data).await?;
+    send_stream.finish()?;
+    Ok(())
+}
+
+/// 发送一帧并等待对方读取完毕，用于发送后马上关闭连接的场景
+async fn send_frame_confirmed(connection: &iroh::endpoint::Connection, data: &[u8]) -> Result<()> {
+    let (mut send_stream, _recv_stream) = connection.open_bi().await?;
+    send_stream.write_all(data).await?;
+    send_stream.finish()?;
+    tokio::time::timeout(SEND_CONFIRM_TIMEOUT, send_stream.stopped())
+        .await
+        .map_err(|_| anyhow::anyhow!("等待对方确认超时"))??;
+    Ok(())
+}
+
+/// 本机的节点 ID，同步服务未运行时用于标记片段的修改者
+pub fn local_node_id() -> Result<NodeId> {
+    Ok(load_secret_key()?.public())
+}
+
+/// 加载本机节点私钥，首次运行时生成并保存
+fn load_secret_key() -> Result<SecretKey> {
+    let path = storage::config_dir()?.join("node.key");
+
+    // 私钥只允许当前用户读取，创建文件时就设置好权限；文件已存在时直接读取
+    match storage::create_private(&path) {
+        Ok(mut file) => {
+            use std::io::Write;
+            let secret_key = SecretKey::generate(rand::rngs::OsRng);
+            file.write_all(hex::encode(secret_key.to_bytes()).as_bytes())
+                .map_err(|e| anyhow::anyhow!("保存节点私钥失败: {}", e))?;
+            Ok(secret_key)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let mut bytes = [0u8; 32];
+            hex::decode_to_slice(std::fs::read_to_string(&path)?.trim(), &mut bytes)
+                .map_err(|e| anyhow::anyhow!("节点私钥格式错误: {}", e))?;
+            Ok(SecretKey::from_bytes(&bytes))
+        }
+        Err(e) => Err(anyhow::anyhow!("创建节点私钥文件失败: {}", e)),
+    }
+}
+
+/// 组成员证明
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupProof {
+    #[serde(default)]
+    pub tag: String, // 组的发现标识，见 SyncGroup::discovery_tag
+    pub proof: String,
+}
+
+/// 连接建立后交换的问候
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerHello {
+    pub device_name: String,
+    pub groups: Vec<GroupProof>,
+    #[serde(default)]
+    pub ticket: Option<String>, // 主动连接时出示的票据
+    #[serde(default)]
+    pub pairing: Option<String>, // 配对码握手消息
+}
+
+/// 直连流上传输的数据帧
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum WireMessage {
+    Hello(PeerHello),
+    PairingConfirm(String),
+    Clipboard(ClipboardMessage),
+    Snippets(Vec<Snippet>),
+}
+
+impl WireMessage {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(Into::into)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).map_err(Into::into)
+    }
+}
+
 /// 剪贴板内容类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClipboardContent {
@@ -104,14 +899,56 @@ impl std::fmt::Display for ClipboardContent {
 }
 
 impl ClipboardContent {
-    /// 获取内容长度（用于预览）
-    pub fn preview_length(&self) -> usize {
+    /// 从文件或标准输入读到的数据构造内容：能识别的图片转为 PNG，其余按 UTF-8 文本处理
+    ///
+    /// 格式只按开头几个字节猜测，`BM`、`P1` 这类开头的普通文本也会被猜成图片，
+    /// 因此解码失败时仍按文本处理。
+    pub fn from_input(data: Vec<u8>) -> Result<Self> {
+        let decoded = image::guess_format(&data)
+            .ok()
+            .map(|format| (format, image::load_from_memory_with_format(&data, format)));
+        if let Some((format, Ok(image))) = decoded {
+            let (width, height) = (image.width(), image.height());
+            let data = if format == image::ImageFormat::Png {
+                data
+            } else {
+                let mut png = std::io::Cursor::new(Vec::new());
+                image
+                    .write_to(&mut png, image::ImageFormat::Png)
+                    .map_err(|e| anyhow::anyhow!("图片转换为 PNG 失败: {}", e))?;
+                png.into_inner()
+            };
+            return Ok(ClipboardContent::Image { width, height, data });
+        }
+
+        let text = String::from_utf8(data).map_err(|_| match decoded {
+            Some((_, Err(e))) => anyhow::anyhow!("图片解码失败: {}", e),
+            _ => anyhow::anyhow!("输入既不是图片也不是 UTF-8 文本"),
+        })?;
+        if text.is_empty() {
+            anyhow::bail!("输入为空");
+        }
+        Ok(ClipboardContent::Text(text))
+    }
+
+    /// 计算内容哈希（blake3，十六进制）
+    pub fn hash(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
         match self {
-            ClipboardContent::Text(text) => text.len(),
-            ClipboardContent::Image { .. } => 50, // 图片固定长度
+            ClipboardContent::Text(text) => {
+                hasher.update(b"text:");
+                hasher.update(text.as_bytes());
+            }
+            ClipboardContent::Image { width, height, data } => {
+                hasher.update(b"image:");
+                hasher.update(&width.to_le_bytes());
+                hasher.update(&height.to_le_bytes());
+                hasher.update(data);
+            }
         }
+        hasher.finalize().to_hex().to_string()
     }
-    
+
     /// 获取内容预览字符串
     pub fn preview(&self, max_length: usize) -> String {
         match self {
@@ -135,34 +972,133 @@ impl ClipboardContent {
 /// 剪贴板同步消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardMessage {
+    pub id: String, // 全局唯一的消息 ID
+    pub content_hash: String, // 内容哈希
     pub content: ClipboardContent,
     pub timestamp: u64, // Unix 时间戳
     pub sender_id: String, // 发送者标识
+    #[serde(default)]
+    pub hops: u8, // 已被转发的跳数
+    #[serde(default)]
+    pub group: Option<String>, // 所属同步组
+    #[serde(default)]
+    pub group_tag: Option<String>, // 组标签，证明消息来自组成员
+    #[serde(default)]
+    pub sensitive: bool, // 疑似敏感内容，不在日志中输出
+    #[serde(default)]
+    pub ttl_secs: Option<u64>, // 接收方在多少秒后清除该内容
+    #[serde(default)]
+    pub incognito: bool, // 隐身模式下的内容，不写入日志和历史
+    #[serde(default)]
+    pub selection: Selection, // 内容来自哪个选区
+    #[serde(default)]
+    pub origin: Option<NodeId>, // 发出消息的节点，转发时保持不变
+    #[serde(default)]
+    pub signature: Option<String>, // 发出节点对消息的签名，见 signing_bytes
+}
+
+/// 参与签名的消息字段，转发时会变化的跳数不参与签名
+#[derive(Serialize)]
+struct MessageBody<'a> {
+    id: &'a str,
+    content_hash: &'a str,
+    timestamp: u64,
+    sender_id: &'a str,
+    origin: &'a Option<NodeId>,
+    group: &'a Option<String>,
+    sensitive: bool,
+    ttl_secs: Option<u64>,
+    incognito: bool,
+    selection: Selection,
 }
 
 impl ClipboardMessage {
-    /// 创建文本消息
-    pub fn new_text(content: String, sender_id: String) -> Self {
+    /// 创建消息，生成新的消息 ID 并计算内容哈希
+    pub fn new(content: ClipboardContent, sender_id: String) -> Self {
         Self {
-            content: ClipboardContent::Text(content),
+            id: uuid::Uuid::new_v4().to_string(),
+            content_hash: content.hash(),
+            content,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
             sender_id,
+            hops: 0,
+            group: None,
+            group_tag: None,
+            sensitive: false,
+            ttl_secs: None,
+            incognito: false,
+            selection: Selection::Clipboard,
+            origin: None,
+            signature: None,
         }
     }
+
+    /// 使用本节点私钥签名，接收方据此确认消息来源和清除时间等字段未被转发节点篡改
+    pub fn sign(&mut self, secret_key: &SecretKey) -> Result<()> {
+        self.origin = Some(secret_key.public());
+        let signature = secret_key.sign(&self.signing_bytes()?);
+        self.signature = Some(hex::encode(signature.to_bytes()));
+        Ok(())
+    }
+
+    /// 校验签名，返回签名有效时的发出节点
+    pub fn verified_origin(&self) -> Option<NodeId> {
+        let origin = self.origin?;
+        let mut bytes = [0u8; 64];
+        hex::decode_to_slice(self.signature.as_deref()?, &mut bytes).ok()?;
+        let signature = ed25519_dalek::Signature::from_bytes(&bytes);
+        origin
+            .verify(&self.signing_bytes().ok()?, &signature)
+            .ok()
+            .map(|()| origin)
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>> {
+        let body = MessageBody {
+            id: &self.id,
+            content_hash: &self.content_hash,
+            timestamp: self.timestamp,
+            sender_id: &self.sender_id,
+            origin: &self.origin,
+            group: &self.group,
+            sensitive: self.sensitive,
+            ttl_secs: self.ttl_secs,
+            incognito: self.incognito,
+            selection: self.selection,
+        };
+        let mut bytes = MESSAGE_SIGNING_CONTEXT.to_vec();
+        bytes.extend(serde_json::to_vec(&body)?);
+        Ok(bytes)
+    }
+
+    /// 用于日志的内容摘要，按日志设置隐藏内容
+    pub fn log_summary(&self) -> String {
+        logging::content_summary(&self.content, self.sensitive)
+    }
+
+    /// 为消息标记所属同步组
+    pub fn with_group(mut self, group: &SyncGroup) -> Self {
+        self.group_tag = Some(group.message_tag(&self.id, &self.content_hash));
+        self.group = Some(group.name.clone());
+        self
+    }
+
+    /// 创建文本消息
+    pub fn new_text(content: String, sender_id: String) -> Self {
+        Self::new(ClipboardContent::Text(content), sender_id)
+    }
     
     /// 创建图片消息
-    pub fn new_image(width: u32, height: u32, data: Vec<u8>, sender_id: String) -> Self {
-        Self {
-            content: ClipboardContent::Image { width, height, data },
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            sender_id,
-        }
+    pub fn new_image(width: u32, height: u32, data: Vec<u8>, sender_id: String) -> Self {
+        Self::new(ClipboardContent::Image { width, height, data }, sender_id)
+    }
+
+    /// 校验内容哈希是否与内容一致
+    pub fn verify_hash(&self) -> bool {
+        self.content.hash() == self.content_hash
     }
 
     /// 序列化为字节
@@ -176,105 +1112,276 @@ impl ClipboardMessage {
     }
 }
 
-/// 网络连接票据 - 用于设备间连接
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConnectionTicket {
-    pub node_id: NodeId,
-    pub addresses: Vec<SocketAddr>,
-}
-
-impl ConnectionTicket {
-    /// 序列化为字符串
-    pub fn to_string(&self) -> Result<String> {
-        let json = serde_json::to_string(self)?;
-        use base64::Engine;
-        Ok(base64::engine::general_purpose::STANDARD.encode(json))
-    }
-
-    /// 从字符串反序列化
-    pub fn from_string(s: &str) -> Result<Self> {
-        use base64::Engine;
-        let json = base64::engine::general_purpose::STANDARD.decode(s).map_err(|e| anyhow::anyhow!("Base64解码失败: {}", e))?;
-        let json_str = String::from_utf8(json)?;
-        serde_json::from_str(&json_str).map_err(Into::into)
-    }
-}
-
 /// P2P 网络管理器
 #[derive(Clone)]
 pub struct NetworkManager {
     router: Router,
     device_name: String,
     protocol: ClipboardProtocol,
-    connections: Arc<Mutex<HashMap<NodeId, iroh::endpoint::Connection>>>,
+    connections: ConnectionMap,
+    options: NetworkOptions,
+    gossip_topics: HashMap<Option<String>, GossipTransport>,
+    pending_sensitive: Arc<Mutex<Option<ClipboardMessage>>>,
 }
 
 impl NetworkManager {
     /// 创建新的网络管理器
-    pub async fn new(device_name: String) -> Result<Self> {
-        println!("正在启动 P2P 网络...");
+    pub async fn new(device_name: String, options: NetworkOptions) -> Result<Self> {
+        info!("正在启动 P2P 网络...");
         
-        // 创建 endpoint，启用本地网络发现
-        let endpoint = Endpoint::builder()
-            .discovery_local_network() // 这是关键！启用局域网设备发现
+        // 创建 endpoint，按配置启用本地网络发现
+        let mut builder = Endpoint::builder().secret_key(load_secret_key()?); // 固定节点身份，票据签名和设备信任都依赖它
+        if options.local_discovery {
+            builder = builder.discovery_local_network(); // 这是关键！启用局域网设备发现
+        }
+        let endpoint = builder
             .bind()
             .await
             .map_err(|e| anyhow::anyhow!("网络初始化失败: {}", e))?;
 
-        println!("网络节点 ID: {}", endpoint.node_id());
+        info!("网络节点 ID: {}", endpoint.node_id());
+
+        // 在局域网发现中声明本机是剪贴板同步节点
+        let announcement = ServiceAnnouncement::new(&device_name, &options.groups);
+        endpoint.set_user_data_for_discovery(Some(announcement.to_user_data()?));
         
+        if options.relay {
+            info!("已启用多跳转发，最大跳数: {}", options.max_hops);
+        }
+
+        // gossip 模式下未加入命名组时必须提供组密钥
+        let use_gossip = options.transport == TransportMode::Gossip;
+        if use_gossip && options.groups.is_empty() && options.group_secret.is_none() {
+            anyhow::bail!("gossip 模式需要提供组密钥 (--group-secret) 或同步组 (--group)");
+        }
+
         // 创建协议处理器
-        let protocol = ClipboardProtocol::new();
+        let connections: ConnectionMap = Arc::new(Mutex::new(HashMap::new()));
+        let protocol = ClipboardProtocol::new(
+            connections.clone(),
+            options.clone(),
+            TrustStore::load()?,
+            UsedTickets::load()?,
+            SnippetStore::load()?,
+            endpoint.node_id(),
+            device_name.clone(),
+        );
         
         // 创建 Router
-        let router = Router::builder(endpoint)
-            .accept(CLIPBOARD_ALPN, protocol.clone())
-            .spawn();
+        let mut router_builder = Router::builder(endpoint.clone())
+            .accept(CLIPBOARD_ALPN, protocol.clone());
+
+        let mut gossip_handle = None;
+        if use_gossip {
+            let gossip = iroh_gossip::net::Gossip::builder()
+                .max_message_size(gossip::GOSSIP_MAX_MESSAGE_SIZE)
+                .spawn(endpoint);
+            router_builder = router_builder.accept(iroh_gossip::ALPN, gossip.clone());
+            gossip_handle = Some(gossip);
+        }
+
+        let router = router_builder.spawn();
+
+        // 每个同步组对应一个由组密钥派生的 gossip 主题
+        let mut gossip_topics = HashMap::new();
+        if let Some(handle) = gossip_handle {
+            if options.groups.is_empty() {
+                if let Some(secret) = &options.group_secret {
+                    let topic = gossip::topic_from_secret(secret.as_bytes());
+                    info!("已启用 gossip 模式，主题: {}", topic.fmt_short());
+                    let transport = GossipTransport::join(&handle, topic, vec![], protocol.clone()).await?;
+                    gossip_topics.insert(None, transport);
+                }
+            } else {
+                for group in &options.groups {
+                    let topic = gossip::topic_from_secret(&group.key);
+                    info!("已启用 gossip 模式，同步组 {} 主题: {}", group.name, topic.fmt_short());
+                    let transport = GossipTransport::join(&handle, topic, vec![], protocol.clone()).await?;
+                    gossip_topics.insert(Some(group.name.clone()), transport);
+                }
+            }
+        }
         
         Ok(Self {
             router,
             device_name,
             protocol,
-            connections: Arc::new(Mutex::new(HashMap::new())),
+            connections,
+            options,
+            gossip_topics,
+            pending_sensitive: Arc::new(Mutex::new(None)),
         })
     }
 
+    /// 配对码和自动发现依赖局域网发现
+    fn require_local_discovery(&self) -> Result<()> {
+        if !self.options.local_discovery {
+            anyhow::bail!("配置中已关闭局域网发现 (transport.local_discovery = false)");
+        }
+        Ok(())
+    }
+
     /// 获取当前节点信息
     pub fn get_node_id(&self) -> NodeId {
         self.router.endpoint().node_id()
     }
 
-    /// 生成连接票据
-    pub async fn generate_ticket(&self) -> Result<String> {
-        // 使用简化的方法，只提供节点ID
-        let node_id = self.router.endpoint().node_id();
-        let ticket = ConnectionTicket {
-            node_id,
-            addresses: vec![], // 暂时为空，依赖iroh的发现机制
-        };
+    /// 生成连接票据，包含当前地址、有效期和节点签名
+    pub async fn generate_ticket(&self, ttl: Duration, single_use: bool) -> Result<String> {
+        let endpoint = self.router.endpoint();
+
+        // 等待本机地址确定，超时则只提供节点 ID，依赖发现机制
+        let node_addr = tokio::time::timeout(Duration::from_secs(5), endpoint.node_addr().initialized())
+            .await
+            .unwrap_or_else(|_| NodeAddr::new(endpoint.node_id()));
+
+        let ticket = ConnectionTicket::issue(endpoint.secret_key(), &node_addr, ttl, single_use)?;
         ticket.to_string()
     }
 
-    /// 连接到其他设备
+    /// 签发配对码，并在局域网发现中声明会合编号，直到配对码被使用或过期
+    pub async fn issue_pairing_code(&self, ttl: Duration) -> Result<String> {
+        if !self.gossip_topics.is_empty() {
+            anyhow::bail!("配对码仅支持直连模式");
+        }
+        self.require_local_discovery()?;
+
+        let code = PairingCode::generate();
+        let announcement = ServiceAnnouncement::new(&self.device_name, &self.options.groups);
+        let pairing_data = announcement.clone().with_rendezvous(code.nameplate()).to_user_data()?;
+        let base_data = announcement.to_user_data()?;
+        *self.protocol.pairing.lock().await = Some(IssuedCode::new(code.clone(), ttl));
+
+        let endpoint = self.router.endpoint().clone();
+        endpoint.set_user_data_for_discovery(Some(pairing_data));
+
+        // 配对码用完或过期后撤下会合编号，签发了新配对码时交给新的任务处理
+        let pairing = self.protocol.pairing.clone();
+        let issued = code.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let mut current = pairing.lock().await;
+                match current.as_ref() {
+                    Some(active) if active.code != issued => return,
+                    Some(active) if !active.is_expired() => continue,
+                    Some(_) => {
+                        *current = None;
+                        info!("配对码 {} 已过期", issued);
+                    }
+                    None => {}
+                }
+                endpoint.set_user_data_for_discovery(Some(base_data));
+                return;
+            }
+        });
+
+        Ok(code.to_string())
+    }
+
+    /// 连接到其他设备，可以使用连接票据或配对码
     pub async fn connect_to_peer(&self, ticket_str: &str) -> Result<()> {
+        if let Some(code) = PairingCode::parse(ticket_str) {
+            return self.connect_with_code(code).await;
+        }
+
         let ticket = ConnectionTicket::from_string(ticket_str)?;
+
+        // 拒绝过期或被篡改的票据
+        ticket.verify()?;
         
         // 构建节点地址
-        let node_addr = NodeAddr::new(ticket.node_id).with_direct_addresses(ticket.addresses);
+        let node_addr = ticket.node_addr();
         
-        println!("正在连接到设备: {}", ticket.node_id);
+        info!("正在连接到设备: {}", ticket.node_id);
+
+        // gossip 模式下只需把对方加入主题
+        if !self.gossip_topics.is_empty() {
+            self.router.endpoint().add_node_addr(node_addr)?;
+            for gossip in self.gossip_topics.values() {
+                gossip.join_peers(vec![ticket.node_id]).await?;
+            }
+            info!("已将设备加入 gossip 主题");
+            return Ok(());
+        }
         
         // 连接到目标节点，使用正确的ALPN
         let connection = self.router.endpoint().connect(node_addr, CLIPBOARD_ALPN).await?;
         
-        println!("成功连接到设备！");
+        info!("成功连接到设备！");
+
+        // 主动使用票据连接的设备视为可信
+        self.protocol
+            .trust
+            .lock()
+            .await
+            .approve(&ticket.node_id, "未知设备")?;
         
-        // 保存连接
-        self.connections.lock().await.insert(ticket.node_id, connection);
+        // 接收对方发来的消息，同时向对方出示票据
+        self.track_connection(connection, Some(Credential::Ticket(ticket_str.trim().to_string())))
+            .await;
         
         Ok(())
     }
 
+    /// 使用配对码连接：在局域网中找到声明了对应会合编号的设备，再用配对码握手
+    async fn connect_with_code(&self, code: PairingCode) -> Result<()> {
+        if !self.gossip_topics.is_empty() {
+            anyhow::bail!("配对码仅支持直连模式");
+        }
+        self.require_local_discovery()?;
+
+        info!("正在局域网中寻找配对码 {} 对应的设备...", code);
+        let my_node_id = self.get_node_id();
+        let mut discovery_stream = self.router.endpoint().discovery_stream();
+        let find_peer = async {
+            while let Some(event_result) = discovery_stream.next().await {
+                let Ok(discovered_node) = event_result else {
+                    continue;
+                };
+                let Some(announcement) = discovered_node
+                    .user_data()
+                    .and_then(|data| ServiceAnnouncement::parse(data.as_ref()))
+                else {
+                    continue;
+                };
+                if discovered_node.node_id() != my_node_id
+                    && announcement.rendezvous.as_deref() == Some(code.nameplate())
+                {
+                    return Some((discovered_node.node_id(), announcement.device_name));
+                }
+            }
+            None
+        };
+        let (node_id, device_name) = tokio::time::timeout(PAIRING_DISCOVERY_TIMEOUT, find_peer)
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("未在局域网中找到配对码对应的设备"))?;
+
+        info!("正在连接到设备: {} ({})", device_name, node_id);
+        let connection = self
+            .router
+            .endpoint()
+            .connect(NodeAddr::new(node_id), CLIPBOARD_ALPN)
+            .await?;
+
+        // 双方确认配对码一致后才会互相加入可信设备
+        self.track_connection(connection, Some(Credential::PairingCode(code))).await;
+
+        Ok(())
+    }
+
+    /// 在后台接收对方通过主动建立的连接发来的消息
+    ///
+    /// 连接在 handle_connection 中通过认证后才会登记，配对完成前不会收到任何同步内容。
+    async fn track_connection(&self, connection: iroh::endpoint::Connection, credential: Option<Credential>) {
+        let protocol = self.protocol.clone();
+        tokio::spawn(async move {
+            protocol.handle_connection(connection, credential).await;
+        });
+    }
+
     /// 初始化消息处理器
     pub async fn setup_message_handler(&self) -> mpsc::UnboundedReceiver<ClipboardMessage> {
         let (tx, rx) = mpsc::unbounded_channel();
@@ -282,91 +1389,223 @@ impl NetworkManager {
         rx
     }
 
-    /// 监听传入的连接 - Router会自动处理
-    pub async fn listen_for_connections(&self) -> Result<()> {
-        // Router已经在后台自动处理连接，这里只是保持连接活跃
-        println!("网络路由器已启动，正在监听连接...");
-        
-        // 保持连接状态
-        loop {
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    /// 发送剪贴板消息到所有连接的设备
+    pub async fn broadcast_message(&self, mut message: ClipboardMessage) -> Result<()> {
+        {
+            let control = self.protocol.sync_control.lock().await;
+            if control.outbound_paused() {
+                debug!("发送已暂停，不广播本机内容");
+                return Ok(());
+            }
+            message.incognito |= control.is_incognito();
         }
-    }
-    
 
-    /// 发送剪贴板消息到所有连接的设备
-    pub async fn broadcast_message(&self, message: ClipboardMessage) -> Result<()> {
-        let data = message.to_bytes()?;
-        
-        // 记录日志
-        match &message.content {
-            ClipboardContent::Text(text) => {
-                println!("广播文本内容: {}", text);
+        {
+            let rules = self.protocol.rules.lock().await;
+            if !rules.filters.allows(&message.content) {
+                debug!("内容被过滤规则排除，不广播");
+                return Ok(());
             }
-            ClipboardContent::Image { width, height, .. } => {
-                println!("广播图片内容: {}x{}", width, height);
+            if !rules.limits.allows(&message.content) {
+                info!("内容超过大小限制，已跳过同步");
+                return Ok(());
             }
         }
+
+        // 记录自己发出的消息，避免被回传后重复应用
+        self.protocol.mark_seen(&message).await;
+        self.protocol.history.record(&message, HistorySource::Local).await;
         
-        // 向所有连接的设备发送消息
-        let connections = self.connections.lock().await;
-        let mut failed_connections = Vec::new();
-        
-        for (node_id, connection) in connections.iter() {
-            // 为每个连接打开一个新的双向流
-            match connection.open_bi().await {
-                Ok((mut send_stream, _recv_stream)) => {
-                    match send_stream.write_all(&data).await {
-                        Ok(_) => {
-                            println!("消息已发送到: {}", node_id);
-                            let _ = send_stream.finish();
-                        }
-                        Err(e) => {
-                            eprintln!("发送到 {} 失败: {}", node_id, e);
-                            failed_connections.push(*node_id);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("打开流到 {} 失败: {}", node_id, e);
-                    failed_connections.push(*node_id);
-                }
-            }
+        // 记录日志
+        if !message.incognito {
+            info!(content = %message.log_summary(), "广播剪贴板内容");
         }
-        
-        // 清理失败的连接
-        drop(connections);
-        if !failed_connections.is_empty() {
-            let mut connections = self.connections.lock().await;
-            for node_id in failed_connections {
-                connections.remove(&node_id);
-            }
+
+        // 未指定组的消息发往本机所在的每个组
+        if message.group.is_some() || self.options.groups.is_empty() {
+            return self.publish(message).await;
+        }
+        for group in &self.options.groups {
+            self.publish(message.clone().with_group(group)).await?;
         }
         
         Ok(())
     }
 
+    /// 发送单条消息：gossip 模式下发布到对应主题，否则发送给已连接的组成员
+    ///
+    /// gossip 主题内的消息由邻居转发，无法按设备限制发送，设备策略只在接收时生效。
+    async fn publish(&self, mut message: ClipboardMessage) -> Result<()> {
+        message.sign(self.router.endpoint().secret_key())?;
+        match self.gossip_topics.get(&message.group) {
+            Some(gossip) => gossip.broadcast(message.to_bytes()?).await,
+            None => self.protocol.send_message(&message, None).await,
+        }
+    }
+
     /// 广播文本内容到所有连接的设备
-    pub async fn broadcast_clipboard(&self, content: &str) -> Result<()> {
-        let message = ClipboardMessage::new_text(
+    ///
+    /// 疑似密码、密钥等敏感内容按配置的策略跳过、静默同步或等待确认。
+    pub async fn broadcast_clipboard(&self, content: &str, selection: Selection) -> Result<()> {
+        let mut message = ClipboardMessage::new_text(
             content.to_string(), 
             self.device_name.clone()
         );
+        message.selection = selection;
+        let rules = self.protocol.rules.lock().await.clone();
+        message.ttl_secs = rules.clear_after.map(|ttl| ttl.as_secs());
+
+        if let Some(kind) = sensitive::detect(content) {
+            message.sensitive = true;
+            if message.ttl_secs.is_none() {
+                message.ttl_secs = rules.sensitive_clear_after.map(|ttl| ttl.as_secs());
+            }
+            match rules.sensitive_policy {
+                SensitivePolicy::Skip => {
+                    info!("🔒 检测到疑似敏感内容（{}），已跳过同步", kind);
+                    return Ok(());
+                }
+                SensitivePolicy::SyncQuiet => {
+                    info!("🔒 检测到疑似敏感内容（{}），同步但不记录内容", kind);
+                }
+                SensitivePolicy::Ask => {
+                    info!("🔒 检测到疑似敏感内容（{}），已暂缓同步", kind);
+                    info!("   运行 `clipboard-sync sensitive allow` 同步，或 `clipboard-sync sensitive deny` 放弃");
+                    *self.pending_sensitive.lock().await = Some(message);
+                    return Ok(());
+                }
+            }
+        }
+
         self.broadcast_message(message).await
     }
+
+    /// 将指定内容直接发送给其他设备，`peers` 为空时发给所有已连接的设备
+    ///
+    /// 这是用户明确要求的发送，不受暂停、过滤规则和敏感内容策略限制，但仍遵守设备的同步策略。
+    /// 指定 `ttl` 时接收方最迟在这之后清除内容，配置的清除时间更短时以配置为准。
+    /// 直连模式下等待每台设备读取完毕，返回送达的设备数；gossip 模式下无法统计，返回 None。
+    pub async fn send_content(
+        &self,
+        content: ClipboardContent,
+        peers: &[NodeId],
+        ttl: Option<Duration>,
+    ) -> Result<Option<usize>> {
+        let mut message = ClipboardMessage::new(content, self.device_name.clone());
+        if let ClipboardContent::Text(text) = &message.content {
+            message.sensitive = sensitive::detect(text).is_some();
+        }
+        let rules = self.protocol.rules.lock().await.clone();
+        let configured = rules
+            .clear_after
+            .or(rules.sensitive_clear_after.filter(|_| message.sensitive));
+        message.ttl_secs = configured
+            .into_iter()
+            .chain(ttl)
+            .min()
+            .map(|ttl| ttl.as_secs());
+        message.incognito = self.protocol.sync_control.lock().await.is_incognito();
+
+        self.protocol.mark_seen(&message).await;
+        self.protocol.history.record(&message, HistorySource::Local).await;
+        if !message.incognito {
+            info!(content = %message.log_summary(), "发送剪贴板内容");
+        }
+
+        let mut messages = if self.options.groups.is_empty() {
+            vec![message]
+        } else {
+            self.options
+                .groups
+                .iter()
+                .map(|group| message.clone().with_group(group))
+                .collect()
+        };
+
+        if !self.gossip_topics.is_empty() {
+            if !peers.is_empty() {
+                anyhow::bail!("gossip 模式下无法指定接收设备");
+            }
+            for message in messages {
+                self.publish(message).await?;
+            }
+            return Ok(None);
+        }
+
+        for message in &mut messages {
+            message.sign(self.router.endpoint().secret_key())?;
+        }
+
+        // 同一设备可能属于多个组，只发送一次
+        let mut sent = HashSet::new();
+        for message in &messages {
+            let targets: Vec<NodeId> = self
+                .connections
+                .lock()
+                .await
+                .keys()
+                .filter(|node_id| !sent.contains(*node_id) && (peers.is_empty() || peers.contains(node_id)))
+                .copied()
+                .collect();
+            let delivered = self
+                .protocol
+                .send_to(message, |node_id| targets.contains(&node_id), true)
+                .await?;
+            sent.extend(delivered);
+        }
+        Ok(Some(sent.len()))
+    }
+
+    /// 连接指定设备并等待对方的问候，返回在超时前完成问候的设备
+    pub async fn connect_peers(&self, peers: &[NodeId], timeout: Duration) -> Vec<NodeId> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        for node_id in peers {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if tokio::time::timeout(remaining, self.try_connect_to_clipboard_node(*node_id))
+                .await
+                .is_err()
+            {
+                warn!("连接到节点 {} 超时", node_id);
+            }
+        }
+
+        // 收到问候后才知道对方所属的组
+        loop {
+            let greeted: Vec<NodeId> = {
+                let peer_groups = self.protocol.peer_groups.lock().await;
+                peers.iter().filter(|node_id| peer_groups.contains_key(*node_id)).copied().collect()
+            };
+            if greeted.len() == peers.len() || tokio::time::Instant::now() >= deadline {
+                return greeted;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// 处理等待确认的敏感内容，返回是否有待确认的内容
+    pub async fn resolve_sensitive(&self, allow: bool) -> Result<bool> {
+        let Some(message) = self.pending_sensitive.lock().await.take() else {
+            return Ok(false);
+        };
+        if allow {
+            self.broadcast_message(message).await?;
+        }
+        Ok(true)
+    }
     
     /// 广播图片内容到所有连接的设备
     pub async fn broadcast_image(&self, width: u32, height: u32, data: Vec<u8>) -> Result<()> {
-        let message = ClipboardMessage::new_image(
+        let mut message = ClipboardMessage::new_image(
             width, 
             height, 
             data, 
             self.device_name.clone()
         );
+        message.ttl_secs = self.protocol.rules.lock().await.clear_after.map(|ttl| ttl.as_secs());
         self.broadcast_message(message).await
     }
 
-    /// 尝试连接到一个可能的其他剪贴板节点
+    /// 尝试连接到其他剪贴板节点
     pub async fn try_connect_to_clipboard_node(&self, node_id: NodeId) -> Result<()> {
         // 检查是否已经连接
         if self.connections.lock().await.contains_key(&node_id) {
@@ -378,7 +1617,15 @@ impl NetworkManager {
             return Ok(());
         }
         
-        println!("尝试连接到发现的节点: {}", node_id);
+        info!("尝试连接到发现的节点: {}", node_id);
+
+        // gossip 模式下由 gossip 负责建立连接
+        if !self.gossip_topics.is_empty() {
+            for gossip in self.gossip_topics.values() {
+                gossip.join_peers(vec![node_id]).await?;
+            }
+            return Ok(());
+        }
         
         // 构建节点地址（只有NodeId，依赖iroh的发现机制找到地址）
         let node_addr = NodeAddr::new(node_id);
@@ -386,15 +1633,14 @@ impl NetworkManager {
         // 尝试连接
         match self.router.endpoint().connect(node_addr, CLIPBOARD_ALPN).await {
             Ok(connection) => {
-                println!("✅ 成功连接到节点: {}", node_id);
+                info!("✅ 成功连接到节点: {}", node_id);
                 
-                // 保存连接
-                self.connections.lock().await.insert(node_id, connection);
+                // 接收对方发来的消息，认证通过后登记连接
+                self.track_connection(connection, None).await;
                 Ok(())
             }
             Err(e) => {
-                // 连接失败是正常的，可能这个节点不是剪贴板同步程序
-                println!("❌ 连接到节点 {} 失败: {} (可能不是剪贴板同步程序)", node_id, e);
+                warn!("❌ 连接到节点 {} 失败: {}", node_id, e);
                 Err(e.into())
             }
         }
@@ -402,16 +1648,17 @@ impl NetworkManager {
     
     /// 自动发现并连接局域网内的其他剪贴板同步节点
     pub async fn start_auto_discovery(&self) -> Result<()> {
-        println!("🔍 启动自动发现服务...");
+        self.require_local_discovery()?;
+        info!("🔍 启动自动发现服务...");
         
         let my_node_id = self.get_node_id();
-        println!("💻 本机节点 ID: {}", my_node_id);
+        info!("💻 本机节点 ID: {}", my_node_id);
         
         // 获取发现事件流
         let mut discovery_stream = self.router.endpoint().discovery_stream();
         let connections = self.connections.clone();
         
-        println!("🌐 正在扫描局域网内的其他设备...");
+        info!("🌐 正在扫描局域网内的其他设备...");
         
         // 处理发现事件
         while let Some(event_result) = discovery_stream.next().await {
@@ -428,30 +1675,414 @@ impl NetworkManager {
                     if connections.lock().await.contains_key(&discovered_node_id) {
                         continue;
                     }
+
+                    // 只连接声明了剪贴板同步服务且与本机同组的节点
+                    let Some(announcement) = discovered_node
+                        .user_data()
+                        .and_then(|data| ServiceAnnouncement::parse(data.as_ref()))
+                    else {
+                        continue;
+                    };
+                    if !announcement.matches(&self.options.groups) {
+                        continue;
+                    }
+
+                    // 未批准的设备等待用户确认
+                    if !self.protocol.is_trusted(&discovered_node_id).await {
+                        self.protocol
+                            .record_discovered(discovered_node_id, &announcement.device_name)
+                            .await;
+                        continue;
+                    }
                     
-                    println!("🎆 发现新设备: {}", discovered_node_id);
+                    info!("🎆 发现剪贴板同步设备: {} ({})", announcement.device_name, discovered_node_id);
                     
-                    // 尝试连接
-                    if let Err(_) = self.try_connect_to_clipboard_node(discovered_node_id).await {
-                        // 连接失败是正常的，可能不是剪贴板同步程序
-                    }
+                    // 失败原因已在 try_connect_to_clipboard_node 中输出
+                    let _ = self.try_connect_to_clipboard_node(discovered_node_id).await;
                 },
                 Err(e) => {
-                    eprintln!("发现服务错误: {}", e);
+                    warn!("发现服务错误: {}", e);
                 }
             }
         }
         
-        println!("🚫 发现流结束");
+        info!("🚫 发现流结束");
+        Ok(())
+    }
+
+    /// 暂停同步，duration 为 None 时直到手动恢复
+    pub async fn pause_sync(&self, direction: PauseDirection, duration: Option<Duration>) {
+        self.protocol.sync_control.lock().await.pause(direction, duration);
+    }
+
+    /// 恢复同步
+    pub async fn resume_sync(&self, direction: PauseDirection) {
+        self.protocol.sync_control.lock().await.resume(direction);
+    }
+
+    /// 开启或关闭隐身模式
+    pub async fn set_incognito(&self, enabled: bool) {
+        self.protocol.sync_control.lock().await.set_incognito(enabled);
+    }
+
+    /// 当前同步状态的说明
+    pub async fn sync_status(&self) -> String {
+        self.protocol.sync_control.lock().await.describe()
+    }
+
+    /// 最近同步过的内容
+    pub fn history(&self) -> History {
+        self.protocol.history.clone()
+    }
+
+    /// 新建或修改片段，保存后发给已连接的设备
+    ///
+    /// 片段只通过直连同步，gossip 模式下不会发送。
+    pub async fn set_snippet(&self, name: &str, content: ClipboardContent) -> Result<()> {
+        let snippet = {
+            let mut store = self.protocol.snippets.lock().await;
+            let snippet = store.set(name, content, &self.protocol.node_id.to_string());
+            store.save()?;
+            snippet
+        };
+        self.protocol.push_snippets(&[snippet], |_| true).await;
+        Ok(())
+    }
+
+    /// 删除片段并通知已连接的设备，返回片段是否存在
+    pub async fn remove_snippet(&self, name: &str) -> Result<bool> {
+        let snippet = {
+            let mut store = self.protocol.snippets.lock().await;
+            let Some(snippet) = store.remove(name, &self.protocol.node_id.to_string()) else {
+                return Ok(false);
+            };
+            store.save()?;
+            snippet
+        };
+        self.protocol.push_snippets(&[snippet], |_| true).await;
+        Ok(true)
+    }
+
+    /// 按名称取片段内容
+    pub async fn snippet(&self, name: &str) -> Option<ClipboardContent> {
+        self.protocol.snippets.lock().await.get(name).cloned()
+    }
+
+    /// 替换同步规则和可信设备列表，返回因不再受信任而断开的设备数
+    pub async fn update_rules(&self, rules: SyncRules, trust: TrustStore) -> usize {
+        self.protocol.update_rules(rules, trust).await
+    }
+
+    /// 设置可信设备的同步策略，返回设备名
+    pub async fn set_peer_policy(&self, node_id: &str, policy: PeerPolicy) -> Result<String> {
+        self.protocol.trust.lock().await.set_policy(node_id, policy)
+    }
+
+    /// 发现但尚未批准的设备
+    pub async fn discovered_devices(&self) -> Vec<DiscoveredDevice> {
+        let discovered = self.protocol.discovered.lock().await.clone();
+        let mut pending = Vec::new();
+        for device in discovered {
+            if !self.protocol.is_trusted(&device.node_id).await {
+                pending.push(device);
+            }
+        }
+        pending
+    }
+
+    /// 已批准的可信设备
+    pub async fn trusted_devices(&self) -> Vec<TrustedDevice> {
+        self.protocol.trust.lock().await.devices().to_vec()
+    }
+
+    /// 批准设备并尝试连接，目标可以是发现列表中的编号或节点 ID，返回设备名
+    pub async fn approve_device(&self, target: &str) -> Result<String> {
+        let (node_id, device_name) = {
+            let discovered = self.protocol.discovered.lock().await;
+            match target.parse::<usize>() {
+                Ok(number) => discovered
+                    .iter()
+                    .find(|device| device.number == number)
+                    .map(|device| (device.node_id, device.device_name.clone()))
+                    .ok_or_else(|| anyhow::anyhow!("无效的设备编号: {}", target))?,
+                Err(_) => {
+                    let node_id: NodeId = target
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("无效的设备编号或节点 ID: {}", target))?;
+                    // 不在发现列表中的节点也允许直接批准
+                    let device_name = discovered
+                        .iter()
+                        .find(|device| device.node_id == node_id)
+                        .map(|device| device.device_name.clone())
+                        .unwrap_or_else(|| "未知设备".to_string());
+                    (node_id, device_name)
+                }
+            }
+        };
+
+        self.protocol
+            .trust
+            .lock()
+            .await
+            .approve(&node_id, &device_name)?;
+        self.protocol.revoked.lock().await.remove(&node_id);
+        info!("✅ 已批准设备: {} ({})", device_name, node_id);
+
+        // 失败原因已在 try_connect_to_clipboard_node 中输出，对方也批准本机后会自动连上
+        let _ = self.try_connect_to_clipboard_node(node_id).await;
+
+        Ok(device_name)
+    }
+
+    /// 撤销对设备的信任并断开连接
+    ///
+    /// 撤销后本次运行中不再接受该设备发出或转发的消息。gossip 模式下设备凭组密钥加入，
+    /// 不一定在可信列表中，此时仍会断开并屏蔽它，但需要更换组密钥才能彻底移除。
+    pub async fn revoke_device(&self, node_id: &str) -> Result<()> {
+        let revoked = self.protocol.trust.lock().await.revoke(node_id);
+        if let Err(e) = revoked {
+            if self.gossip_topics.is_empty() {
+                return Err(e);
+            }
+        }
+
+        let node_id: NodeId = node_id
+            .parse()
+            .map_err(|_| anyhow::anyhow!("无效的节点 ID: {}", node_id))?;
+        self.protocol.revoked.lock().await.insert(node_id);
+        self.protocol.peer_groups.lock().await.remove(&node_id);
+        if let Some(connection) = self.connections.lock().await.remove(&node_id) {
+            connection.close(0u32.into(), b"revoked");
+        }
+
+        if !self.gossip_topics.is_empty() {
+            warn!("设备 {} 持有组密钥，重启后仍可重新加入，请更换组密钥并重新分发给其他设备", node_id);
+        }
         Ok(())
     }
 
     /// 关闭网络管理器
     pub async fn shutdown(self) {
-        println!("正在关闭网络连接...");
+        info!("正在关闭网络连接...");
         if let Err(e) = self.router.shutdown().await {
-            eprintln!("关闭路由器失败: {}", e);
+            error!("关闭路由器失败: {}", e);
+        }
+        info!("网络已关闭");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::path::Path;
+
+    /// 在回环地址上启动只提供剪贴板协议的节点，可信设备等数据保存在 `dir` 中
+    async fn spawn_node(dir: &Path, device_name: &str) -> (Router, ClipboardProtocol) {
+        std::fs::create_dir_all(dir).unwrap();
+        let endpoint = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .bind_addr_v4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0))
+            .bind()
+            .await
+            .unwrap();
+        let options = NetworkOptions {
+            local_discovery: false,
+            ..Default::default()
+        };
+        let protocol = ClipboardProtocol::new(
+            Arc::new(Mutex::new(HashMap::new())),
+            options,
+            TrustStore::load_from(dir.join("trusted_devices.json")).unwrap(),
+            UsedTickets::load_from(dir.join("used_tickets.json")).unwrap(),
+            SnippetStore::load_from(dir.join("snippets.json")).unwrap(),
+            endpoint.node_id(),
+            device_name.to_string(),
+        );
+        let router = Router::builder(endpoint).accept(CLIPBOARD_ALPN, protocol.clone()).spawn();
+        (router, protocol)
+    }
+
+    fn local_addr(router: &Router) -> NodeAddr {
+        let endpoint = router.endpoint();
+        NodeAddr::from_parts(
+            endpoint.node_id(),
+            None,
+            endpoint.bound_sockets().into_iter().filter(|addr| addr.is_ipv4()),
+        )
+    }
+
+    /// 带着票据连接签发方，返回签发方是否接受了连接
+    async fn join_with_ticket(
+        (router, protocol): &(Router, ClipboardProtocol),
+        (issuer_router, issuer): &(Router, ClipboardProtocol),
+        ticket: &str,
+    ) -> bool {
+        // 与 `connect` 命令一致，主动连接的一方信任签发方
+        protocol.trust.lock().await.approve(&issuer.node_id, "台式机").unwrap();
+
+        let connection = router.endpoint().connect(local_addr(issuer_router), CLIPBOARD_ALPN).await.unwrap();
+        let joining = protocol.clone();
+        let credential = Credential::Ticket(ticket.to_string());
+        let handle = connection.clone();
+        tokio::spawn(async move { joining.handle_connection(handle, Some(credential)).await });
+
+        let accepted = async {
+            while !issuer.connections.lock().await.contains_key(&protocol.node_id) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::select! {
+            _ = connection.closed() => false,
+            result = tokio::time::timeout(Duration::from_secs(10), accepted) => result.is_ok(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pairing_code_from_auto_issuer_to_connect_joiner() {
+        let dir = std::env::temp_dir().join(format!("clipboard-sync-pairing-{}", std::process::id()));
+        let (issuer_router, issuer) = spawn_node(&dir.join("issuer"), "台式机").await;
+        let (joiner_router, joiner) = spawn_node(&dir.join("joiner"), "笔记本").await;
+
+        let code = PairingCode::generate();
+        *issuer.pairing.lock().await = Some(IssuedCode::new(code.clone(), Duration::from_secs(60)));
+
+        let connection = joiner_router.endpoint().connect(local_addr(&issuer_router), CLIPBOARD_ALPN).await.unwrap();
+        let joining = joiner.clone();
+        tokio::spawn(async move {
+            joining
+                .handle_connection(connection, Some(Credential::PairingCode(code)))
+                .await;
+        });
+
+        let paired = async {
+            loop {
+                let connected = issuer.connections.lock().await.contains_key(&joiner.node_id)
+                    && joiner.connections.lock().await.contains_key(&issuer.node_id);
+                if connected && issuer.is_trusted(&joiner.node_id).await && joiner.is_trusted(&issuer.node_id).await {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), paired)
+            .await
+            .expect("配对未在限定时间内完成");
+        // 配对码只能使用一次
+        assert!(issuer.active_pairing_code().await.is_none());
+
+        issuer_router.shutdown().await.unwrap();
+        joiner_router.shutdown().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_peer_policy_applies_to_next_message() {
+        let dir = std::env::temp_dir().join(format!("clipboard-sync-policy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let trust_path = dir.join("trusted_devices.json");
+        let laptop = SecretKey::from_bytes(&[7u8; 32]);
+
+        let mut trust = TrustStore::load_from(trust_path.clone()).unwrap();
+        trust.approve(&laptop.public(), "笔记本").unwrap();
+        let protocol = ClipboardProtocol::new(
+            Arc::new(Mutex::new(HashMap::new())),
+            NetworkOptions::default(),
+            trust,
+            UsedTickets::load_from(dir.join("used_tickets.json")).unwrap(),
+            SnippetStore::load_from(dir.join("snippets.json")).unwrap(),
+            SecretKey::from_bytes(&[8u8; 32]).public(),
+            "台式机".to_string(),
+        );
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        protocol.set_message_sender(tx).await;
+        let message = |text: &str| {
+            let mut message = ClipboardMessage::new_text(text.to_string(), "笔记本".to_string());
+            message.sign(&laptop).unwrap();
+            message
+        };
+
+        assert!(protocol.deliver(message("第一条"), None).await);
+        assert!(rx.try_recv().is_ok());
+
+        // 在 trusted_devices.json 中改为不接收笔记本的内容，重新加载后立即生效
+        let policy = PeerPolicy {
+            direction: crate::trust::SyncDirection::SendOnly,
+            ..Default::default()
+        };
+        TrustStore::load_from(trust_path.clone())
+            .unwrap()
+            .set_policy(&laptop.public().to_string(), policy)
+            .unwrap();
+        let reloaded = TrustStore::load_from(trust_path).unwrap();
+        assert_eq!(protocol.update_rules(SyncRules::default(), reloaded).await, 0);
+
+        assert!(!protocol.deliver(message("第二条"), None).await);
+        assert!(rx.try_recv().is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_peer_needs_valid_ticket() {
+        let dir = std::env::temp_dir().join(format!("clipboard-sync-ticket-{}", std::process::id()));
+        let issuer = spawn_node(&dir.join("issuer"), "台式机").await;
+        let secret_key = issuer.0.endpoint().secret_key().clone();
+        let ticket = |ttl, single_use| {
+            ConnectionTicket::issue(&secret_key, &local_addr(&issuer.0), ttl, single_use)
+                .unwrap()
+                .to_string()
+                .unwrap()
+        };
+
+        // 没有票据、票据过期或一次性票据被第二台设备使用时都会被拒绝
+        let stranger = spawn_node(&dir.join("stranger"), "陌生设备").await;
+        assert!(!join_with_ticket(&stranger, &issuer, "").await);
+        assert!(!join_with_ticket(&stranger, &issuer, &ticket(Duration::ZERO, false)).await);
+
+        let single_use = ticket(Duration::from_secs(600), true);
+        let first = spawn_node(&dir.join("first"), "笔记本").await;
+        assert!(join_with_ticket(&first, &issuer, &single_use).await);
+        let second = spawn_node(&dir.join("second"), "平板").await;
+        assert!(!join_with_ticket(&second, &issuer, &single_use).await);
+        assert!(!issuer.1.is_trusted(&second.1.node_id).await);
+
+        for (router, _) in [issuer, stranger, first, second] {
+            router.shutdown().await.unwrap();
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_message_signature_and_tampering() {
+        let secret_key = SecretKey::from_bytes(&[5u8; 32]);
+        let mut message = ClipboardMessage::new_text("验证码 123456".to_string(), "笔记本".to_string());
+        message.ttl_secs = Some(30);
+        assert_eq!(message.verified_origin(), None);
+
+        message.sign(&secret_key).unwrap();
+        assert_eq!(message.verified_origin(), Some(secret_key.public()));
+
+        // 转发时增加跳数不影响签名
+        let mut relayed = message.clone();
+        relayed.hops += 1;
+        assert!(relayed.verified_origin().is_some());
+
+        let mut tampered = message.clone();
+        tampered.ttl_secs = None;
+        assert_eq!(tampered.verified_origin(), None);
+
+        let mut forged = message.clone();
+        forged.origin = Some(SecretKey::from_bytes(&[6u8; 32]).public());
+        assert_eq!(forged.verified_origin(), None);
+    }
+
+    #[test]
+    fn test_text_with_image_magic_is_kept_as_text() {
+        // 分别以 BMP 和 PBM 的文件头开头
+        for text in ["BMW 车辆保养提醒", "P1 outage: 数据库连接超时"] {
+            let content = ClipboardContent::from_input(text.as_bytes().to_vec()).unwrap();
+            assert!(matches!(content, ClipboardContent::Text(t) if t == text));
         }
-        println!("网络已关闭");
+        assert!(ClipboardContent::from_input(vec![b'B', b'M', 0xff, 0xfe]).is_err());
     }
 }
@@ -1,49 +1,56 @@
 use anyhow::Result;
 use notify_rust::Notification;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::config::NotificationSettings;
 
 /// 通知管理器
 #[derive(Clone)]
 pub struct NotificationManager {
-    enabled: bool,
+    settings: Arc<Mutex<NotificationSettings>>,
 }
 
 impl NotificationManager {
-    pub fn new() -> Self {
-        Self { enabled: true }
+    pub fn new(settings: &NotificationSettings) -> Self {
+        Self {
+            settings: Arc::new(Mutex::new(settings.clone())),
+        }
+    }
+
+    /// 替换通知设置，配置重新加载时使用
+    pub fn update(&self, settings: &NotificationSettings) {
+        *self.settings.lock().unwrap() = settings.clone();
+    }
+
+    /// 通知中文本预览的最大字符数
+    pub fn preview_length(&self) -> usize {
+        self.settings.lock().unwrap().preview_length
     }
 
     /// 发送系统通知
     pub fn send(&self, title: &str, message: &str) -> Result<()> {
-        if !self.enabled {
+        let settings = self.settings.lock().unwrap().clone();
+        if !settings.enabled {
             return Ok(());
         }
 
-        println!("🔔 {}: {}", title, message); // 先在控制台显示
+        info!("🔔 {}", title); // 通知正文可能包含剪贴板内容，不写入日志
 
         // 尝试发送系统通知
         match Notification::new()
             .summary(title)
             .body(message)
-            .timeout(3000) // 3秒后消失
+            .timeout(i32::try_from(settings.timeout_ms).unwrap_or(i32::MAX))
             .show()
         {
             Ok(_) => {}
             Err(e) => {
                 // 如果系统通知失败，不要崩溃程序
-                eprintln!("系统通知发送失败: {}", e);
+                warn!("系统通知发送失败: {}", e);
             }
         }
 
         Ok(())
     }
-
-    /// 启用/禁用通知
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
-    }
-
-    /// 检查是否启用通知
-    pub fn is_enabled(&self) -> bool {
-        self.enabled
-    }
 }
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use std::io::Write;
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::clipboard::ClipboardBackend;
+use crate::headless::MemoryClipboard;
+
+/// screen 单个 DCS 序列能转发的最大长度
+const SCREEN_CHUNK_LEN: usize = 768;
+
+/// 需要透传转义序列的终端复用器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// 根据环境变量判断当前是否运行在 tmux 或 screen 中
+    ///
+    /// 不看 TERM：tmux 外的终端也常把 TERM 设为 screen-256color，此时加上 DCS 包装反而会失效。
+    fn detect() -> Self {
+        if std::env::var_os("TMUX").is_some() {
+            Multiplexer::Tmux
+        } else if std::env::var_os("STY").is_some() {
+            Multiplexer::Screen
+        } else {
+            Multiplexer::None
+        }
+    }
+}
+
+/// 通过 OSC 52 转义序列设置终端剪贴板
+///
+/// 适合在 SSH 会话中运行：收到的文字写到控制终端，由本地的终端模拟器放进本机剪贴板。
+/// OSC 52 无法可靠地读取终端剪贴板，也不支持图片，读取时返回最后写入的内容。
+pub struct Osc52Clipboard {
+    multiplexer: Multiplexer,
+    last: MemoryClipboard,
+    tty: Mutex<()>,
+}
+
+impl Osc52Clipboard {
+    pub fn new() -> Self {
+        Self {
+            multiplexer: Multiplexer::detect(),
+            last: MemoryClipboard::default(),
+            tty: Mutex::new(()),
+        }
+    }
+
+    fn emit(&self, payload: &str) -> Result<()> {
+        let sequence = sequence(payload, self.multiplexer);
+        let _guard = self.tty.lock().unwrap();
+        let mut terminal = open_terminal()?;
+        terminal
+            .write_all(sequence.as_bytes())
+            .and_then(|_| terminal.flush())
+            .map_err(|e| anyhow::anyhow!("写入终端失败: {}", e))
+    }
+}
+
+impl ClipboardBackend for Osc52Clipboard {
+    fn get_text(&self) -> Result<String> {
+        self.last.get_text()
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        self.emit(&data_encoding::BASE64.encode(text.as_bytes()))?;
+        self.last.set_text(text)
+    }
+
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        self.last.get_image()
+    }
+
+    fn set_image(&self, width: u32, height: u32, png_data: &[u8]) -> Result<()> {
+        warn!("OSC 52 不支持图片，图片只保存在内存中");
+        self.last.set_image(width, height, png_data)
+    }
+
+    fn clear(&self) -> Result<()> {
+        // 参数不是合法的 base64 时终端会清空剪贴板
+        self.emit("!")?;
+        self.last.clear()
+    }
+}
+
+/// 打开控制终端，标准输出被重定向时也能写到终端上
+#[cfg(unix)]
+fn open_terminal() -> Result<Box<dyn Write>> {
+    let tty = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| anyhow::anyhow!("无法打开控制终端: {}", e))?;
+    Ok(Box::new(tty))
+}
+
+#[cfg(not(unix))]
+fn open_terminal() -> Result<Box<dyn Write>> {
+    Ok(Box::new(std::io::stdout()))
+}
+
+/// 生成设置剪贴板的转义序列，在终端复用器中包装为透传序列
+fn sequence(payload: &str, multiplexer: Multiplexer) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", payload);
+    match multiplexer {
+        Multiplexer::None => osc,
+        // tmux 要求序列中的 ESC 写两次，并且需要开启 allow-passthrough
+        Multiplexer::Tmux => format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b")),
+        // screen 会截断过长的 DCS 序列，需要分段转发
+        Multiplexer::Screen => osc
+            .as_bytes()
+            .chunks(SCREEN_CHUNK_LEN)
+            .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_passthrough() {
+        let payload = data_encoding::BASE64.encode("你好".as_bytes());
+        assert_eq!(sequence(&payload, Multiplexer::None), format!("\x1b]52;c;{}\x07", payload));
+        assert_eq!(
+            sequence(&payload, Multiplexer::Tmux),
+            format!("\x1bPtmux;\x1b\x1b]52;c;{}\x07\x1b\\", payload)
+        );
+
+        let long = "A".repeat(SCREEN_CHUNK_LEN * 2);
+        let screen = sequence(&long, Multiplexer::Screen);
+        assert_eq!(screen.matches("\x1bP").count(), 3);
+        assert_eq!(screen.replace("\x1bP", "").replace("\x1b\\", ""), format!("\x1b]52;c;{}\x07", long));
+    }
+}
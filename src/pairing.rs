@@ -0,0 +1,214 @@
+use anyhow::Result;
+use iroh::NodeId;
+use rand::Rng;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::time::Duration;
+
+/// 配对码的默认有效期
+pub const DEFAULT_CODE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// 密钥协商的身份标识，双方必须一致
+const PAKE_IDENTITY: &[u8] = b"clipboard-sync pairing v1";
+
+/// 会合编号的上限，编号在局域网发现中公开，用于找到签发配对码的设备
+const MAX_NAMEPLATE: u32 = 99;
+
+/// 配对码使用的单词表，两个单词共 16 位熵，按字母顺序排列以便二分查找
+const WORDS: [&str; 256] = [
+    "acorn", "admiral", "alpine", "amber", "anchor", "apple", "apricot", "arctic",
+    "arrow", "aspen", "atlas", "aurora", "autumn", "avenue", "badger", "bamboo",
+    "banjo", "barley", "basil", "beacon", "beaver", "birch", "biscuit", "bison",
+    "blossom", "bonfire", "boulder", "bramble", "breeze", "bridge", "bronze", "buffalo",
+    "butter", "cabin", "cactus", "camel", "candle", "canyon", "captain", "caramel",
+    "carbon", "cargo", "carrot", "castle", "cedar", "cello", "chalk", "cherry",
+    "chestnut", "cinder", "circus", "citrus", "clover", "cobalt", "coconut", "comet",
+    "copper", "coral", "cosmos", "cotton", "cougar", "crane", "crater", "crimson",
+    "crystal", "cypress", "dahlia", "daisy", "delta", "desert", "diamond", "dolphin",
+    "dragon", "drum", "dune", "dusk", "eagle", "echo", "ember", "emerald",
+    "engine", "falcon", "feather", "fennel", "fern", "festival", "fiddle", "flint",
+    "forest", "fossil", "fountain", "fox", "galaxy", "garden", "garnet", "gazelle",
+    "ginger", "glacier", "glade", "granite", "grape", "gravel", "guitar", "hammer",
+    "harbor", "harp", "harvest", "hazel", "heron", "hickory", "honey", "horizon",
+    "husky", "iceberg", "indigo", "iris", "island", "ivory", "jade", "jaguar",
+    "jasmine", "jelly", "jungle", "juniper", "kayak", "kettle", "kite", "kiwi",
+    "koala", "lagoon", "lantern", "lark", "lava", "lemon", "lilac", "lily",
+    "lime", "linen", "lion", "lobster", "lotus", "lunar", "magnet", "mango",
+    "maple", "marble", "meadow", "melon", "meteor", "mint", "mirror", "monsoon",
+    "moose", "mosaic", "moss", "mountain", "mulberry", "nebula", "nectar", "nickel",
+    "noodle", "nutmeg", "oak", "oasis", "ocean", "olive", "onyx", "orange",
+    "orbit", "orchid", "otter", "owl", "oyster", "paddle", "panda", "papaya",
+    "parrot", "peach", "pebble", "pelican", "pepper", "piano", "pilot", "pine",
+    "planet", "plum", "polar", "pony", "poppy", "prairie", "prism", "pumpkin",
+    "quartz", "quill", "rabbit", "radar", "rainbow", "raven", "reef", "ribbon",
+    "river", "robin", "rocket", "rose", "ruby", "saddle", "saffron", "salmon",
+    "sapphire", "satellite", "savanna", "scarlet", "sequoia", "shadow", "shell", "sierra",
+    "silver", "sparrow", "spruce", "squirrel", "summit", "sunset", "swan", "tangerine",
+    "teapot", "thistle", "thunder", "tiger", "timber", "topaz", "tulip", "tundra",
+    "turtle", "twilight", "umbrella", "valley", "velvet", "violet", "volcano", "walnut",
+    "walrus", "willow", "window", "winter", "wizard", "wombat", "yacht", "yellow",
+    "yogurt", "zebra", "zenith", "zephyr", "zinc", "zipper", "zodiac", "zucchini",
+];
+
+/// 配对码，例如 `7-crimson-harbor`
+///
+/// 开头的编号是公开的会合编号，签发方在局域网发现中声明它；后面的单词只通过
+/// 口头或聊天传递，双方用整串配对码做 SPAKE2 密钥协商，不会在网络上暴露。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingCode(String);
+
+impl PairingCode {
+    /// 生成随机配对码
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let nameplate = rng.gen_range(1..=MAX_NAMEPLATE);
+        let first = WORDS[rng.gen_range(0..WORDS.len())];
+        let second = WORDS[rng.gen_range(0..WORDS.len())];
+        Self(format!("{}-{}-{}", nameplate, first, second))
+    }
+
+    /// 解析用户输入的配对码，格式不符时返回 None
+    pub fn parse(s: &str) -> Option<Self> {
+        let code = s.trim().to_lowercase();
+        let mut parts = code.split('-');
+        let nameplate: u32 = parts.next()?.parse().ok()?;
+        let words: Vec<&str> = parts.collect();
+        if !(1..=MAX_NAMEPLATE).contains(&nameplate)
+            || words.len() != 2
+            || !words.iter().all(|word| WORDS.binary_search(word).is_ok())
+        {
+            return None;
+        }
+        Some(Self(code))
+    }
+
+    /// 公开的会合编号
+    pub fn nameplate(&self) -> &str {
+        self.0.split('-').next().unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for PairingCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// 本机签发、等待对方使用的配对码
+#[derive(Debug, Clone)]
+pub struct IssuedCode {
+    pub code: PairingCode,
+    pub expires_at: u64, // Unix 时间戳
+}
+
+impl IssuedCode {
+    pub fn new(code: PairingCode, ttl: Duration) -> Self {
+        Self {
+            code,
+            expires_at: unix_now() + ttl.as_secs(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        unix_now() >= self.expires_at
+    }
+}
+
+/// 基于配对码的 SPAKE2 握手
+///
+/// 双方各发送一条握手消息，只有配对码相同时才能得到相同的会话密钥。
+/// 猜错的一方每次连接只能尝试一次，无法离线穷举配对码。
+pub struct PairingHandshake {
+    state: Spake2<Ed25519Group>,
+}
+
+impl PairingHandshake {
+    /// 开始握手，返回需要发送给对方的握手消息（十六进制）
+    pub fn start(code: &PairingCode) -> (Self, String) {
+        let (state, message) = Spake2::<Ed25519Group>::start_symmetric(
+            &Password::new(code.as_str()),
+            &Identity::new(PAKE_IDENTITY),
+        );
+        (Self { state }, hex::encode(message))
+    }
+
+    /// 使用对方的握手消息完成握手，得到会话密钥
+    pub fn finish(self, peer_message: &str) -> Result<PairingKey> {
+        let message = hex::decode(peer_message).map_err(|e| anyhow::anyhow!("握手消息格式错误: {}", e))?;
+        let key = self
+            .state
+            .finish(&message)
+            .map_err(|e| anyhow::anyhow!("配对握手失败: {:?}", e))?;
+        let key: [u8; 32] = key
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("会话密钥长度错误"))?;
+        Ok(PairingKey(key))
+    }
+}
+
+/// 配对握手得到的会话密钥，用于确认双方输入了相同的配对码
+pub struct PairingKey([u8; 32]);
+
+impl PairingKey {
+    /// 本机的确认码，绑定发送方节点 ID，防止被原样反射回来
+    pub fn confirmation(&self, node_id: &NodeId) -> String {
+        self.confirmation_hash(node_id).to_hex().to_string()
+    }
+
+    /// 校验对方的确认码
+    pub fn verify_confirmation(&self, node_id: &NodeId, confirmation: &str) -> bool {
+        let expected = self.confirmation_hash(node_id);
+        blake3::Hash::from_hex(confirmation).is_ok_and(|confirmation| confirmation == expected)
+    }
+
+    fn confirmation_hash(&self, node_id: &NodeId) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(&self.0);
+        hasher.update(b"confirm:");
+        hasher.update(node_id.as_bytes());
+        hasher.finalize()
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_parsing_and_handshake() {
+        // 单词表必须严格递增，parse 依赖二分查找，同时保证没有重复
+        assert!(WORDS.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let code = PairingCode::generate();
+        assert_eq!(PairingCode::parse(&code.as_str().to_uppercase()), Some(code.clone()));
+        assert!(PairingCode::parse("7-crimson").is_none());
+        assert!(PairingCode::parse("7-crimson-notaword").is_none());
+        assert_eq!(PairingCode::parse(" 7-Crimson-Harbor ").unwrap().nameplate(), "7");
+
+        let issuer = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let joiner = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+
+        let (a, message_a) = PairingHandshake::start(&code);
+        let (b, message_b) = PairingHandshake::start(&code);
+        let key_a = a.finish(&message_b).unwrap();
+        let key_b = b.finish(&message_a).unwrap();
+        assert!(key_b.verify_confirmation(&issuer, &key_a.confirmation(&issuer)));
+        assert!(!key_b.verify_confirmation(&joiner, &key_a.confirmation(&issuer)));
+
+        let wrong = PairingCode::parse("7-crimson-harbor").unwrap();
+        let (a, message_a) = PairingHandshake::start(&PairingCode::parse("7-crimson-island").unwrap());
+        let (b, message_b) = PairingHandshake::start(&wrong);
+        let key_a = a.finish(&message_b).unwrap();
+        let key_b = b.finish(&message_a).unwrap();
+        assert!(!key_b.verify_confirmation(&issuer, &key_a.confirmation(&issuer)));
+    }
+}
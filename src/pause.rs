@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 暂停同步的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseDirection {
+    /// 不再广播本机的剪贴板变化
+    Outbound,
+    /// 不再应用其他设备发来的内容
+    Inbound,
+    /// 双向暂停
+    #[default]
+    Both,
+}
+
+impl PauseDirection {
+    fn outbound(self) -> bool {
+        matches!(self, PauseDirection::Outbound | PauseDirection::Both)
+    }
+
+    fn inbound(self) -> bool {
+        matches!(self, PauseDirection::Inbound | PauseDirection::Both)
+    }
+}
+
+/// 单个方向的暂停状态，None 表示无限期暂停
+#[derive(Debug, Clone, Copy)]
+struct Pause {
+    until: Option<Instant>,
+}
+
+impl Pause {
+    fn is_active(&self) -> bool {
+        self.until.is_none_or(|until| Instant::now() < until)
+    }
+
+    fn describe(&self) -> String {
+        match self.until {
+            None => "已暂停".to_string(),
+            Some(until) => {
+                let remaining = until.saturating_duration_since(Instant::now()).as_secs();
+                format!("已暂停，{} 分 {} 秒后恢复", remaining / 60, remaining % 60)
+            }
+        }
+    }
+}
+
+/// 运行时的同步开关：暂停、恢复和隐身模式
+#[derive(Debug, Default)]
+pub struct SyncControl {
+    outbound: Option<Pause>,
+    inbound: Option<Pause>,
+    incognito: bool,
+}
+
+impl SyncControl {
+    /// 暂停指定方向的同步，duration 为 None 时直到手动恢复
+    pub fn pause(&mut self, direction: PauseDirection, duration: Option<Duration>) {
+        let pause = Pause {
+            until: duration.map(|duration| Instant::now() + duration),
+        };
+        if direction.outbound() {
+            self.outbound = Some(pause);
+        }
+        if direction.inbound() {
+            self.inbound = Some(pause);
+        }
+    }
+
+    /// 恢复指定方向的同步
+    pub fn resume(&mut self, direction: PauseDirection) {
+        if direction.outbound() {
+            self.outbound = None;
+        }
+        if direction.inbound() {
+            self.inbound = None;
+        }
+    }
+
+    /// 是否暂停广播本机内容，定时暂停到期后自动恢复
+    pub fn outbound_paused(&self) -> bool {
+        self.outbound.is_some_and(|pause| pause.is_active())
+    }
+
+    /// 是否暂停应用收到的内容
+    pub fn inbound_paused(&self) -> bool {
+        self.inbound.is_some_and(|pause| pause.is_active())
+    }
+
+    pub fn set_incognito(&mut self, enabled: bool) {
+        self.incognito = enabled;
+    }
+
+    /// 隐身模式下同步的内容不写入日志和历史
+    pub fn is_incognito(&self) -> bool {
+        self.incognito
+    }
+
+    /// 当前状态的说明
+    pub fn describe(&self) -> String {
+        let state = |pause: &Option<Pause>| match pause {
+            Some(pause) if pause.is_active() => pause.describe(),
+            _ => "同步中".to_string(),
+        };
+        format!(
+            "发送: {}\n接收: {}\n隐身模式: {}",
+            state(&self.outbound),
+            state(&self.inbound),
+            if self.incognito { "开启" } else { "关闭" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_directions_and_expiry() {
+        let mut control = SyncControl::default();
+        control.pause(PauseDirection::Outbound, None);
+        assert!(control.outbound_paused());
+        assert!(!control.inbound_paused());
+
+        control.pause(PauseDirection::Both, Some(Duration::ZERO));
+        assert!(!control.outbound_paused());
+        assert!(!control.inbound_paused());
+
+        control.pause(PauseDirection::Inbound, Some(Duration::from_secs(600)));
+        assert!(control.inbound_paused());
+        control.resume(PauseDirection::Both);
+        assert!(!control.inbound_paused());
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// 本机不支持 PRIMARY 选区时，收到的 PRIMARY 内容如何处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryFallback {
+    /// 忽略
+    #[default]
+    Ignore,
+    /// 写入剪贴板
+    Clipboard,
+}
+
+/// PRIMARY 选区的防抖
+///
+/// 选中文字时 PRIMARY 会随着拖动不断变化，内容保持不变足够久之后才广播。
+#[derive(Debug, Default)]
+pub struct Debouncer {
+    pending: Option<(String, Instant)>,
+    last_sent: String,
+}
+
+impl Debouncer {
+    /// 把内容视为已经广播过，用于其他设备发来、由本机写入选区的内容
+    pub fn seed(&mut self, text: String) {
+        self.pending = None;
+        self.last_sent = text;
+    }
+
+    /// 记录当前选区内容，返回需要广播的内容
+    pub fn update(&mut self, text: String, delay: Duration, now: Instant) -> Option<String> {
+        if text.is_empty() || text == self.last_sent {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == text => *since,
+            _ => {
+                self.pending = Some((text.clone(), now));
+                now
+            }
+        };
+        if now.duration_since(since) < delay {
+            return None;
+        }
+
+        self.pending = None;
+        self.last_sent = text.clone();
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer_waits_for_stable_selection() {
+        let delay = Duration::from_millis(500);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::default();
+
+        assert_eq!(debouncer.update("he".to_string(), delay, at(0)), None);
+        assert_eq!(debouncer.update("hello".to_string(), delay, at(300)), None);
+        assert_eq!(debouncer.update("hello".to_string(), delay, at(600)), None);
+        assert_eq!(debouncer.update("hello".to_string(), delay, at(800)), Some("hello".to_string()));
+        assert_eq!(debouncer.update("hello".to_string(), delay, at(2000)), None);
+
+        assert_eq!(debouncer.update("world".to_string(), Duration::ZERO, at(2100)), Some("world".to_string()));
+    }
+
+    #[test]
+    fn test_seeded_selection_is_not_sent() {
+        let delay = Duration::from_millis(500);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::default();
+
+        assert_eq!(debouncer.update("本机选中".to_string(), delay, at(0)), None);
+        debouncer.seed("同步过来的".to_string());
+        assert_eq!(debouncer.update("同步过来的".to_string(), delay, at(100)), None);
+        assert_eq!(debouncer.update("同步过来的".to_string(), delay, at(1000)), None);
+        assert_eq!(debouncer.update("本机选中".to_string(), Duration::ZERO, at(1100)), Some("本机选中".to_string()));
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{Color, QrCode};
+use std::path::Path;
+
+/// 二维码四周的空白宽度（模块数）
+const QUIET_ZONE: usize = 4;
+
+/// PNG 中每个模块的像素大小
+const PNG_MODULE_SIZE: usize = 8;
+
+/// 使用 Unicode 半高方块将内容渲染为终端二维码
+///
+/// 深色背景的终端更常见，因此反转颜色：亮色字符表示深色模块。
+pub fn render_terminal(data: &str) -> Result<String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| anyhow::anyhow!("生成二维码失败: {}", e))?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// 将内容保存为二维码 PNG 图片
+pub fn save_png(data: &str, path: &Path) -> Result<()> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| anyhow::anyhow!("生成二维码失败: {}", e))?;
+    let width = code.width();
+    let colors = code.to_colors();
+
+    let size = ((width + QUIET_ZONE * 2) * PNG_MODULE_SIZE) as u32;
+    let image = image::GrayImage::from_fn(size, size, |x, y| {
+        let module_x = (x as usize / PNG_MODULE_SIZE).checked_sub(QUIET_ZONE);
+        let module_y = (y as usize / PNG_MODULE_SIZE).checked_sub(QUIET_ZONE);
+        let dark = match (module_x, module_y) {
+            (Some(mx), Some(my)) if mx < width && my < width => colors[my * width + mx] == Color::Dark,
+            _ => false,
+        };
+        image::Luma([if dark { 0 } else { 255 }])
+    });
+
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| anyhow::anyhow!("保存二维码图片失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_terminal_and_png() {
+        let rendered = render_terminal("CS1HELLO").unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines.len() > 10);
+        assert!(lines.iter().all(|line| line.chars().count() == lines[0].chars().count()));
+
+        let path = std::env::temp_dir().join(format!("clipboard-sync-qr-{}.png", std::process::id()));
+        save_png("CS1HELLO", &path).unwrap();
+        let image = image::open(&path).unwrap();
+        assert_eq!(image.width(), image.height());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::{Config, ConfigSource, PrimarySettings};
+use crate::network::NetworkManager;
+use crate::notification::NotificationManager;
+use crate::trust::TrustStore;
+
+/// 检查配置文件是否被修改的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 运行中服务的配置热更新
+///
+/// 过滤规则、可信设备及其同步策略、通知设置、PRIMARY 选区设置和轮询间隔立即生效，
+/// 已建立的连接保持不变；设备名称、同步组和传输设置仍需重启服务。
+/// 可信设备和同步策略保存在 trusted_devices.json 中，重新加载时一并读取。
+#[derive(Clone)]
+pub struct ConfigReloader {
+    source: ConfigSource,
+    current: Arc<Mutex<Config>>,
+    network: NetworkManager,
+    notifier: NotificationManager,
+}
+
+impl ConfigReloader {
+    pub fn new(source: ConfigSource, config: Config, network: NetworkManager, notifier: NotificationManager) -> Self {
+        Self {
+            source,
+            current: Arc::new(Mutex::new(config)),
+            network,
+            notifier,
+        }
+    }
+
+    /// 当前的剪贴板轮询间隔
+    pub async fn poll_interval(&self) -> Duration {
+        self.current.lock().await.poll_interval()
+    }
+
+    /// 当前的 PRIMARY 选区同步设置
+    pub async fn primary(&self) -> PrimarySettings {
+        self.current.lock().await.primary.clone()
+    }
+
+    /// 重新读取配置并应用，新配置无效时保留当前配置并返回错误
+    pub async fn reload(&self) -> Result<String> {
+        let config = self.source.load()?;
+        let trust = TrustStore::load()?;
+
+        let mut current = self.current.lock().await;
+        let restart_required = current.restart_required(&config);
+        let disconnected = self.network.update_rules(config.sync_rules(), trust).await;
+        self.notifier.update(&config.notifications);
+        *current = config;
+
+        let mut message = "配置已重新加载".to_string();
+        if disconnected > 0 {
+            message.push_str(&format!("，已断开 {} 台不再受信任的设备", disconnected));
+        }
+        if !restart_required.is_empty() {
+            message.push_str(&format!("，以下设置需要重启服务才能生效: {}", restart_required.join(", ")));
+        }
+        Ok(message)
+    }
+
+    /// 监视配置文件，修改后自动重新加载
+    pub async fn watch(self) {
+        let path = match self.source.path() {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("无法监视配置文件: {}", e);
+                return;
+            }
+        };
+
+        let mut last_modified = modified_time(&path);
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            let modified = modified_time(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            // 配置文件被删除时继续使用当前配置
+            if modified.is_none() {
+                continue;
+            }
+            match self.reload().await {
+                Ok(message) => info!("{}", message),
+                Err(e) => warn!("配置重新加载失败，继续使用当前配置: {}", e),
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::network::ClipboardContent;
+use crate::storage;
+
+/// 一条命名片段，如地址、模板或常用命令
+///
+/// 删除时保留一条没有内容的记录，让删除也能同步到其他设备。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub content: Option<ClipboardContent>, // None 表示已删除
+    pub updated_at: u64, // 最后修改的 Unix 时间戳（毫秒）
+    pub updated_by: String, // 最后修改的设备节点 ID，时间相同时用于决定先后
+}
+
+impl Snippet {
+    /// 是否比另一个版本更新，修改时间相同时按设备节点 ID 决定，保证各设备合并结果一致
+    fn is_newer_than(&self, other: &Snippet) -> bool {
+        (self.updated_at, &self.updated_by) > (other.updated_at, &other.updated_by)
+    }
+}
+
+/// 在可信设备之间同步的片段库，与同步历史分开保存
+#[derive(Debug)]
+pub struct SnippetStore {
+    path: PathBuf,
+    snippets: Vec<Snippet>,
+}
+
+impl SnippetStore {
+    /// 从配置目录加载片段
+    pub fn load() -> Result<Self> {
+        Self::load_from(storage::config_dir()?.join("snippets.json"))
+    }
+
+    /// 从指定文件加载
+    pub fn load_from(path: PathBuf) -> Result<Self> {
+        let snippets = storage::load_json(&path)?;
+        Ok(Self { path, snippets })
+    }
+
+    /// 保存片段，片段中可能有同步过来的敏感内容，文件只允许当前用户读写
+    pub fn save(&self) -> Result<()> {
+        storage::save_json(&self.path, &self.snippets)
+    }
+
+    /// 全部片段，包括已删除的记录，用于与其他设备同步
+    pub fn all(&self) -> &[Snippet] {
+        &self.snippets
+    }
+
+    /// 未删除的片段，按名称排序
+    pub fn list(&self) -> Vec<&Snippet> {
+        let mut snippets: Vec<&Snippet> = self.snippets.iter().filter(|snippet| snippet.content.is_some()).collect();
+        snippets.sort_by(|a, b| a.name.cmp(&b.name));
+        snippets
+    }
+
+    /// 按名称取片段内容
+    pub fn get(&self, name: &str) -> Option<&ClipboardContent> {
+        self.snippets
+            .iter()
+            .find(|snippet| snippet.name == name)
+            .and_then(|snippet| snippet.content.as_ref())
+    }
+
+    /// 新建或修改片段，返回新版本
+    pub fn set(&mut self, name: &str, content: ClipboardContent, updated_by: &str) -> Snippet {
+        self.update(name, Some(content), updated_by)
+    }
+
+    /// 删除片段，返回删除记录；片段不存在时返回 None
+    pub fn remove(&mut self, name: &str, updated_by: &str) -> Option<Snippet> {
+        self.get(name)?;
+        Some(self.update(name, None, updated_by))
+    }
+
+    fn update(&mut self, name: &str, content: Option<ClipboardContent>, updated_by: &str) -> Snippet {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        // 时钟回拨时仍保证新版本比旧版本新
+        let previous = self.snippets.iter().find(|snippet| snippet.name == name);
+        let snippet = Snippet {
+            name: name.to_string(),
+            content,
+            updated_at: previous.map_or(now, |previous| now.max(previous.updated_at + 1)),
+            updated_by: updated_by.to_string(),
+        };
+        self.merge(snippet.clone());
+        snippet
+    }
+
+    /// 合并其他设备的版本，较新的版本生效，返回本地是否有变化
+    pub fn merge(&mut self, incoming: Snippet) -> bool {
+        match self.snippets.iter_mut().find(|snippet| snippet.name == incoming.name) {
+            Some(existing) if incoming.is_newer_than(existing) => {
+                *existing = incoming;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.snippets.push(incoming);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_newest_version() {
+        let mut store = SnippetStore {
+            path: PathBuf::new(),
+            snippets: Vec::new(),
+        };
+        let text = |s: &str| Some(ClipboardContent::Text(s.to_string()));
+        let snippet = |content, updated_at, updated_by: &str| Snippet {
+            name: "地址".to_string(),
+            content,
+            updated_at,
+            updated_by: updated_by.to_string(),
+        };
+
+        assert!(store.merge(snippet(text("旧地址"), 100, "a")));
+        assert!(!store.merge(snippet(text("更旧的地址"), 50, "b")));
+        assert!(store.merge(snippet(text("新地址"), 100, "b")));
+        assert!(matches!(store.get("地址"), Some(ClipboardContent::Text(text)) if text == "新地址"));
+
+        // 删除记录同样按版本合并
+        let removed = store.remove("地址", "a").unwrap();
+        assert!(removed.updated_at > 100);
+        assert!(store.get("地址").is_none());
+        assert!(store.list().is_empty());
+        assert!(!store.merge(snippet(text("新地址"), 100, "b")));
+        assert!(store.remove("地址", "a").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_snippets_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // 片段可能包含同步过来的敏感内容
+        let path = std::env::temp_dir().join(format!("clipboard-sync-snippets-{}.json", std::process::id()));
+        let mut store = SnippetStore::load_from(path.clone()).unwrap();
+        store.set("密码", ClipboardContent::Text("hunter2".to_string()), "a");
+        store.save().unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(SnippetStore::load_from(path.clone()).unwrap().get("密码").is_some());
+        let _ = std::fs::remove_file(path);
+    }
+}
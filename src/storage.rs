@@ -0,0 +1,91 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 获取本程序的配置目录（不存在时自动创建）
+pub fn config_dir() -> Result<PathBuf> {
+    let dir = dirs::config_dir()
+        .ok_or_else(|| anyhow::anyhow!("无法确定用户配置目录"))?
+        .join("clipboard-sync");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow::anyhow!("创建配置目录 {} 失败: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// 从 JSON 文件加载数据，文件不存在时返回默认值
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let data = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", path.display(), e))?;
+    serde_json::from_slice(&data)
+        .map_err(|e| anyhow::anyhow!("解析 {} 失败: {}", path.display(), e))
+}
+
+/// 创建只允许当前用户读写的新文件，文件已存在时返回 `AlreadyExists` 错误
+///
+/// 权限在创建时就已设置，不存在先写入再修改权限的窗口。
+pub fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// 将数据写入只允许当前用户读写的文件（先写临时文件再替换，避免写坏）
+///
+/// 替换后文件使用临时文件的权限，之前以默认权限保存的文件也会随之收紧。
+pub fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    // 在完整文件名后追加后缀，避免与同目录下扩展名为 tmp 的文件冲突
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // 上次写入中断时留下的临时文件权限未知，删除后重新创建
+    match std::fs::remove_file(&tmp_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(anyhow::anyhow!("删除 {} 失败: {}", tmp_path.display(), e)),
+    }
+    create_private(&tmp_path)
+        .and_then(|mut file| file.write_all(data))
+        .map_err(|e| anyhow::anyhow!("写入 {} 失败: {}", tmp_path.display(), e))?;
+    std::fs::rename(&tmp_path, path)
+        .map_err(|e| anyhow::anyhow!("保存 {} 失败: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// 将数据以 JSON 格式写入只允许当前用户读写的文件
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    write_private(path, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_json_tightens_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("clipboard-sync-storage-{}.json", std::process::id()));
+        std::fs::write(&path, "[]").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        save_json(&path, &vec!["组密钥"]).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_json::<Vec<String>>(&path).unwrap(), vec!["组密钥".to_string()]);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use iroh::{NodeAddr, NodeId, RelayUrl, SecretKey};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::storage;
+
+/// 默认的票据有效期
+pub const DEFAULT_TICKET_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// 签名内容的域分隔前缀
+const SIGNING_CONTEXT: &[u8] = b"clipboard-sync ticket v1:";
+
+/// 紧凑票据的前缀，用于和 Base64 形式区分
+const COMPACT_PREFIX: &str = "CS1";
+
+/// 网络连接票据 - 用于设备间连接
+///
+/// 票据包含签发节点当前的直连地址和中继地址、过期时间，并由节点私钥签名。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionTicket {
+    pub node_id: NodeId,
+    pub addresses: Vec<SocketAddr>,
+    pub relay_url: Option<RelayUrl>,
+    pub expires_at: u64, // Unix 时间戳
+    pub single_use: bool,
+    pub nonce: String,
+    pub signature: String,
+}
+
+/// 参与签名的票据字段
+#[derive(Serialize)]
+struct TicketBody<'a> {
+    node_id: &'a NodeId,
+    addresses: &'a [SocketAddr],
+    relay_url: &'a Option<RelayUrl>,
+    expires_at: u64,
+    single_use: bool,
+    nonce: &'a str,
+}
+
+/// 紧凑形式的票据，随机数和签名以原始字节存放，避免十六进制让长度翻倍
+#[derive(Serialize, Deserialize)]
+struct CompactTicket {
+    node_id: NodeId,
+    addresses: Vec<SocketAddr>,
+    relay_url: Option<RelayUrl>,
+    expires_at: u64,
+    single_use: bool,
+    nonce: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl ConnectionTicket {
+    /// 使用节点私钥签发票据
+    pub fn issue(secret_key: &SecretKey, node_addr: &NodeAddr, ttl: Duration, single_use: bool) -> Result<Self> {
+        let mut ticket = Self {
+            node_id: secret_key.public(),
+            addresses: node_addr.direct_addresses.iter().copied().collect(),
+            relay_url: node_addr.relay_url.clone(),
+            expires_at: unix_now() + ttl.as_secs(),
+            single_use,
+            nonce: hex::encode(rand::random::<[u8; 16]>()),
+            signature: String::new(),
+        };
+        let signature = secret_key.sign(&ticket.signing_bytes()?);
+        ticket.signature = hex::encode(signature.to_bytes());
+        Ok(ticket)
+    }
+
+    /// 校验签名和有效期
+    pub fn verify(&self) -> Result<()> {
+        let mut bytes = [0u8; 64];
+        hex::decode_to_slice(&self.signature, &mut bytes)
+            .map_err(|_| anyhow::anyhow!("票据签名格式错误"))?;
+        let signature = ed25519_dalek::Signature::from_bytes(&bytes);
+
+        self.node_id
+            .verify(&self.signing_bytes()?, &signature)
+            .map_err(|_| anyhow::anyhow!("票据签名无效，可能已被篡改"))?;
+
+        if unix_now() >= self.expires_at {
+            anyhow::bail!("票据已过期");
+        }
+
+        Ok(())
+    }
+
+    /// 票据中的节点地址
+    pub fn node_addr(&self) -> NodeAddr {
+        NodeAddr::from_parts(self.node_id, self.relay_url.clone(), self.addresses.iter().copied())
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>> {
+        let body = TicketBody {
+            node_id: &self.node_id,
+            addresses: &self.addresses,
+            relay_url: &self.relay_url,
+            expires_at: self.expires_at,
+            single_use: self.single_use,
+            nonce: &self.nonce,
+        };
+        let mut bytes = SIGNING_CONTEXT.to_vec();
+        bytes.extend(serde_json::to_vec(&body)?);
+        Ok(bytes)
+    }
+
+    /// 序列化为字符串
+    pub fn to_string(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        use base64::Engine;
+        Ok(base64::engine::general_purpose::STANDARD.encode(json))
+    }
+
+    /// 序列化为紧凑字符串
+    ///
+    /// 使用二进制编码加大写 Base32，只包含数字和大写字母，可以直接放进 URL，
+    /// 也能以二维码的字母数字模式编码，比 Base64 形式的二维码小得多。
+    pub fn to_compact_string(&self) -> Result<String> {
+        let compact = CompactTicket {
+            node_id: self.node_id,
+            addresses: self.addresses.clone(),
+            relay_url: self.relay_url.clone(),
+            expires_at: self.expires_at,
+            single_use: self.single_use,
+            nonce: hex::decode(&self.nonce).map_err(|_| anyhow::anyhow!("票据随机数格式错误"))?,
+            signature: hex::decode(&self.signature).map_err(|_| anyhow::anyhow!("票据签名格式错误"))?,
+        };
+        let bytes = postcard::to_stdvec(&compact)?;
+        Ok(format!("{}{}", COMPACT_PREFIX, data_encoding::BASE32_NOPAD.encode(&bytes)))
+    }
+
+    /// 从字符串反序列化，同时支持 Base64 形式和紧凑形式
+    pub fn from_string(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(compact) = s.strip_prefix(COMPACT_PREFIX) {
+            let bytes = data_encoding::BASE32_NOPAD
+                .decode(compact.to_ascii_uppercase().as_bytes())
+                .map_err(|e| anyhow::anyhow!("Base32解码失败: {}", e))?;
+            let compact: CompactTicket =
+                postcard::from_bytes(&bytes).map_err(|e| anyhow::anyhow!("票据格式无效: {}", e))?;
+            return Ok(Self {
+                node_id: compact.node_id,
+                addresses: compact.addresses,
+                relay_url: compact.relay_url,
+                expires_at: compact.expires_at,
+                single_use: compact.single_use,
+                nonce: hex::encode(compact.nonce),
+                signature: hex::encode(compact.signature),
+            });
+        }
+
+        use base64::Engine;
+        let json = base64::engine::general_purpose::STANDARD.decode(s).map_err(|e| anyhow::anyhow!("Base64解码失败: {}", e))?;
+        let json_str = String::from_utf8(json)?;
+        serde_json::from_str(&json_str).map_err(|e| anyhow::anyhow!("票据格式无效: {}", e))
+    }
+}
+
+/// 已兑换的一次性票据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UsedTicket {
+    nonce: String,
+    expires_at: u64,
+}
+
+/// 记录已兑换的一次性票据，防止重复使用
+#[derive(Debug)]
+pub struct UsedTickets {
+    path: PathBuf,
+    tickets: Vec<UsedTicket>,
+}
+
+impl UsedTickets {
+    /// 从配置目录加载
+    pub fn load() -> Result<Self> {
+        Self::load_from(storage::config_dir()?.join("used_tickets.json"))
+    }
+
+    /// 从指定文件加载
+    pub fn load_from(path: PathBuf) -> Result<Self> {
+        let tickets = storage::load_json(&path)?;
+        Ok(Self { path, tickets })
+    }
+
+    /// 标记票据已兑换，若此前已兑换过则返回 false
+    pub fn mark_used(&mut self, ticket: &ConnectionTicket) -> Result<bool> {
+        // 过期的票据本身就无法通过校验，无需继续记录
+        let now = unix_now();
+        self.tickets.retain(|used| used.expires_at > now);
+
+        if self.tickets.iter().any(|used| used.nonce == ticket.nonce) {
+            return Ok(false);
+        }
+        self.tickets.push(UsedTicket {
+            nonce: ticket.nonce.clone(),
+            expires_at: ticket.expires_at,
+        });
+        storage::save_json(&self.path, &self.tickets)?;
+        Ok(true)
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_signature_and_tampering() {
+        let secret_key = SecretKey::from_bytes(&[3u8; 32]);
+        let node_addr = NodeAddr::new(secret_key.public())
+            .with_direct_addresses(["192.168.1.2:4433".parse().unwrap()]);
+
+        let ticket = ConnectionTicket::issue(&secret_key, &node_addr, DEFAULT_TICKET_TTL, true).unwrap();
+        let decoded = ConnectionTicket::from_string(&ticket.to_string().unwrap()).unwrap();
+        assert!(decoded.verify().is_ok());
+        assert_eq!(decoded.node_addr().direct_addresses.len(), 1);
+
+        let compact = ticket.to_compact_string().unwrap();
+        assert!(compact.len() < ticket.to_string().unwrap().len());
+        assert!(compact.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+        let decoded_compact = ConnectionTicket::from_string(&compact).unwrap();
+        assert!(decoded_compact.verify().is_ok());
+        assert_eq!(decoded_compact.nonce, ticket.nonce);
+        // 随机数和签名按原始字节编码，共 80 字节，Base32 后约 128 个字符
+        assert!(compact.len() < 250, "紧凑票据过长: {}", compact.len());
+
+        let mut tampered = decoded.clone();
+        tampered.expires_at += 3600;
+        assert!(tampered.verify().is_err());
+
+        let expired = ConnectionTicket::issue(&secret_key, &node_addr, Duration::ZERO, false).unwrap();
+        assert!(expired.verify().is_err());
+    }
+}
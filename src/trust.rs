@@ -0,0 +1,237 @@
+use anyhow::Result;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::network::ClipboardContent;
+use crate::storage;
+
+/// 与某台设备之间的同步方向（从本机角度）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncDirection {
+    /// 双向同步
+    #[default]
+    Bidirectional,
+    /// 只向该设备发送，不接受它发来的内容
+    SendOnly,
+    /// 只接受该设备发来的内容，不向它发送
+    ReceiveOnly,
+}
+
+/// 剪贴板内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    Text,
+    Image,
+}
+
+impl ContentKind {
+    pub const ALL: [ContentKind; 2] = [ContentKind::Text, ContentKind::Image];
+
+    pub fn of(content: &ClipboardContent) -> Self {
+        match content {
+            ClipboardContent::Text(_) => ContentKind::Text,
+            ClipboardContent::Image { .. } => ContentKind::Image,
+        }
+    }
+}
+
+impl std::fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentKind::Text => write!(f, "文本"),
+            ContentKind::Image => write!(f, "图片"),
+        }
+    }
+}
+
+/// 针对单台设备的同步策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerPolicy {
+    #[serde(default)]
+    pub direction: SyncDirection,
+    #[serde(default = "all_content_kinds")]
+    pub content: Vec<ContentKind>, // 允许同步的内容类型
+}
+
+fn all_content_kinds() -> Vec<ContentKind> {
+    ContentKind::ALL.to_vec()
+}
+
+impl Default for PeerPolicy {
+    fn default() -> Self {
+        Self {
+            direction: SyncDirection::default(),
+            content: all_content_kinds(),
+        }
+    }
+}
+
+impl PeerPolicy {
+    /// 是否允许向该设备发送这类内容
+    pub fn allows_send(&self, content: &ClipboardContent) -> bool {
+        self.direction != SyncDirection::ReceiveOnly && self.content.contains(&ContentKind::of(content))
+    }
+
+    /// 是否接受该设备发来的这类内容
+    pub fn allows_receive(&self, content: &ClipboardContent) -> bool {
+        self.direction != SyncDirection::SendOnly && self.content.contains(&ContentKind::of(content))
+    }
+
+    /// 策略说明，例如 `只发送, 文本`
+    pub fn describe(&self) -> String {
+        let direction = match self.direction {
+            SyncDirection::Bidirectional => "双向",
+            SyncDirection::SendOnly => "只发送",
+            SyncDirection::ReceiveOnly => "只接收",
+        };
+        let content = if self.content.is_empty() {
+            "不同步任何内容".to_string()
+        } else {
+            self.content
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("、")
+        };
+        format!("{}, {}", direction, content)
+    }
+}
+
+/// 已批准的可信设备
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedDevice {
+    pub node_id: String,
+    pub device_name: String,
+    pub approved_at: u64, // Unix 时间戳
+    #[serde(default)]
+    pub policy: PeerPolicy,
+}
+
+/// 局域网内发现但尚未批准的设备
+#[derive(Debug, Clone)]
+pub struct DiscoveredDevice {
+    pub number: usize, // 本次运行中固定不变的编号，用于 `approve <编号>`
+    pub node_id: NodeId,
+    pub device_name: String,
+    pub first_seen: u64, // Unix 时间戳
+}
+
+/// 本地保存的可信设备列表
+#[derive(Debug)]
+pub struct TrustStore {
+    path: PathBuf,
+    devices: Vec<TrustedDevice>,
+}
+
+impl TrustStore {
+    /// 从配置目录加载可信设备
+    pub fn load() -> Result<Self> {
+        Self::load_from(storage::config_dir()?.join("trusted_devices.json"))
+    }
+
+    /// 从指定文件加载
+    pub fn load_from(path: PathBuf) -> Result<Self> {
+        let devices = storage::load_json(&path)?;
+        Ok(Self { path, devices })
+    }
+
+    /// 保存可信设备
+    pub fn save(&self) -> Result<()> {
+        storage::save_json(&self.path, &self.devices)
+    }
+
+    /// 所有可信设备
+    pub fn devices(&self) -> &[TrustedDevice] {
+        &self.devices
+    }
+
+    /// 检查设备是否已被批准
+    pub fn is_trusted(&self, node_id: &NodeId) -> bool {
+        let node_id = node_id.to_string();
+        self.devices.iter().any(|device| device.node_id == node_id)
+    }
+
+    /// 批准设备并保存
+    pub fn approve(&mut self, node_id: &NodeId, device_name: &str) -> Result<()> {
+        if self.is_trusted(node_id) {
+            return Ok(());
+        }
+        self.devices.push(TrustedDevice {
+            node_id: node_id.to_string(),
+            device_name: device_name.to_string(),
+            approved_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            policy: PeerPolicy::default(),
+        });
+        self.save()
+    }
+
+    /// 设备的同步策略，不在可信列表中的设备（如同步组成员）使用默认策略
+    pub fn policy(&self, node_id: &NodeId) -> PeerPolicy {
+        let node_id = node_id.to_string();
+        self.devices
+            .iter()
+            .find(|device| device.node_id == node_id)
+            .map(|device| device.policy.clone())
+            .unwrap_or_default()
+    }
+
+    /// 设置设备的同步策略并保存，返回设备名
+    pub fn set_policy(&mut self, node_id: &str, policy: PeerPolicy) -> Result<String> {
+        let device = self
+            .devices
+            .iter_mut()
+            .find(|device| device.node_id == node_id)
+            .ok_or_else(|| anyhow::anyhow!("设备不在可信列表中: {}", node_id))?;
+        device.policy = policy;
+        let device_name = device.device_name.clone();
+        self.save()?;
+        Ok(device_name)
+    }
+
+    /// 撤销对设备的信任并保存
+    pub fn revoke(&mut self, node_id: &str) -> Result<()> {
+        let before = self.devices.len();
+        self.devices.retain(|device| device.node_id != node_id);
+        if self.devices.len() == before {
+            anyhow::bail!("设备不在可信列表中: {}", node_id);
+        }
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_policy_direction_and_content() {
+        let text = ClipboardContent::Text("hello".to_string());
+        let image = ClipboardContent::Image {
+            width: 1,
+            height: 1,
+            data: vec![0; 4],
+        };
+
+        let policy = PeerPolicy::default();
+        assert!(policy.allows_send(&image) && policy.allows_receive(&image));
+
+        let policy = PeerPolicy {
+            direction: SyncDirection::SendOnly,
+            content: vec![ContentKind::Text],
+        };
+        assert!(policy.allows_send(&text));
+        assert!(!policy.allows_send(&image));
+        assert!(!policy.allows_receive(&text));
+
+        // 旧版本保存的设备没有策略字段，按双向同步全部内容处理
+        let device: TrustedDevice =
+            serde_json::from_str(r#"{"node_id":"a","device_name":"b","approved_at":0}"#).unwrap();
+        assert_eq!(device.policy, PeerPolicy::default());
+    }
+}
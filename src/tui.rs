@@ -0,0 +1,314 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::runtime::Handle;
+
+use crate::control::{self, ControlRequest, ControlResponse};
+use crate::history::{HistoryEntry, HistorySource};
+use crate::network::ClipboardContent;
+
+/// 列表中文本预览的最大字符数
+const LIST_PREVIEW_LENGTH: usize = 60;
+
+/// 浏览运行中服务的同步历史，支持模糊搜索、固定和删除，回车将选中的内容写回剪贴板
+///
+/// broadcast 为 true 时回车写回的同时发给其他设备，否则需要按 Ctrl+B。
+pub async fn run(broadcast: bool) -> Result<()> {
+    // 先确认服务在运行，再进入全屏界面
+    let entries = request(ControlRequest::ListHistory).await?.history;
+    let handle = Handle::current();
+    let message = tokio::task::spawn_blocking(move || {
+        let mut terminal = ratatui::init();
+        let result = Browser::new(entries, broadcast).run(&mut terminal, &handle);
+        ratatui::restore();
+        result
+    })
+    .await??;
+
+    if let Some(message) = message {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+async fn request(request: ControlRequest) -> Result<ControlResponse> {
+    match control::send_request(request).await? {
+        Some(response) if response.ok => Ok(response),
+        Some(response) => anyhow::bail!("{}", response.message),
+        None => anyhow::bail!("同步服务未运行"),
+    }
+}
+
+/// 浏览界面的状态
+struct Browser {
+    entries: Vec<HistoryEntry>,
+    query: String,
+    /// 与搜索条件匹配的条目下标，按显示顺序排列
+    matches: Vec<usize>,
+    list: ListState,
+    broadcast: bool,
+    status: String,
+}
+
+impl Browser {
+    fn new(entries: Vec<HistoryEntry>, broadcast: bool) -> Self {
+        let mut browser = Self {
+            entries,
+            query: String::new(),
+            matches: Vec::new(),
+            list: ListState::default(),
+            broadcast,
+            status: String::new(),
+        };
+        browser.refilter();
+        browser
+    }
+
+    /// 处理按键直到退出，返回退出后要输出的消息
+    fn run(mut self, terminal: &mut DefaultTerminal, handle: &Handle) -> Result<Option<String>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(exit) = self.handle_key(key, handle) {
+                return Ok(exit);
+            }
+        }
+    }
+
+    /// 处理一个按键，需要退出时返回 Some
+    fn handle_key(&mut self, key: KeyEvent, handle: &Handle) -> Option<Option<String>> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(None),
+            KeyCode::Char('c') if ctrl => return Some(None),
+            KeyCode::Up => self.list.select_previous(),
+            KeyCode::Down => self.list.select_next(),
+            KeyCode::Enter => return self.restore(self.broadcast, handle),
+            KeyCode::Char('b') if ctrl => return self.restore(true, handle),
+            KeyCode::Char('p') if ctrl => self.toggle_pin(handle),
+            KeyCode::Char('d') if ctrl => self.delete(handle),
+            KeyCode::Delete => self.delete(handle),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn selected(&self) -> Option<&HistoryEntry> {
+        let index = *self.matches.get(self.list.selected()?)?;
+        self.entries.get(index)
+    }
+
+    /// 写回剪贴板，成功后退出界面
+    fn restore(&mut self, broadcast: bool, handle: &Handle) -> Option<Option<String>> {
+        let hash = self.selected()?.content_hash.clone();
+        match handle.block_on(request(ControlRequest::RestoreHistory { hash, broadcast })) {
+            Ok(response) => Some(Some(response.message)),
+            Err(e) => {
+                self.status = e.to_string();
+                None
+            }
+        }
+    }
+
+    fn toggle_pin(&mut self, handle: &Handle) {
+        let Some(entry) = self.selected() else {
+            return;
+        };
+        let hash = entry.content_hash.clone();
+        let pinned = !entry.pinned;
+        match handle.block_on(request(ControlRequest::PinHistory { hash: hash.clone(), pinned })) {
+            Ok(response) => {
+                if let Some(entry) = self.entries.iter_mut().find(|entry| entry.content_hash == hash) {
+                    entry.pinned = pinned;
+                }
+                self.status = response.message;
+                self.refilter();
+            }
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    fn delete(&mut self, handle: &Handle) {
+        let Some(entry) = self.selected() else {
+            return;
+        };
+        let hash = entry.content_hash.clone();
+        match handle.block_on(request(ControlRequest::DeleteHistory { hash: hash.clone() })) {
+            Ok(response) => {
+                self.entries.retain(|entry| entry.content_hash != hash);
+                self.status = response.message;
+                self.refilter();
+            }
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    /// 按搜索条件重新筛选：没有条件时固定的条目在前，否则按匹配程度排序
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                if self.query.is_empty() {
+                    return Some((entry.pinned as i64, index));
+                }
+                fuzzy_score(&self.query, &search_text(entry)).map(|score| (score, index))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+
+        let selected = self.list.selected().unwrap_or(0);
+        self.list.select((!self.matches.is_empty()).then(|| selected.min(self.matches.len() - 1)));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search_area, list_area, preview_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let search = Paragraph::new(self.query.as_str()).block(Block::default().borders(Borders::ALL).title("搜索"));
+        frame.render_widget(search, search_area);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|index| list_line(&self.entries[*index], now))
+            .map(ListItem::new)
+            .collect();
+        let title = format!("同步历史 ({}/{})", self.matches.len(), self.entries.len());
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let preview = Paragraph::new(self.selected().map(preview_text).unwrap_or_default())
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("预览"));
+        frame.render_widget(preview, preview_area);
+
+        let help = if self.status.is_empty() {
+            let enter = if self.broadcast { "写回并发送" } else { "写回剪贴板" };
+            format!("↑↓ 选择  Enter {}  Ctrl+B 写回并发送  Ctrl+P 固定  Ctrl+D 删除  Esc 退出", enter)
+        } else {
+            self.status.clone()
+        };
+        frame.render_widget(Paragraph::new(help).style(Style::default().fg(Color::DarkGray)), help_area);
+    }
+}
+
+fn source_name(source: &HistorySource) -> &str {
+    match source {
+        HistorySource::Local => "本机",
+        HistorySource::Remote(name) => name,
+    }
+}
+
+/// 模糊搜索匹配的文字：内容和来源设备
+fn search_text(entry: &HistoryEntry) -> String {
+    let content = match &entry.content {
+        ClipboardContent::Text(text) => text.clone(),
+        ClipboardContent::Image { width, height, .. } => format!("图片 {}x{}", width, height),
+    };
+    format!("{} {}", content, source_name(&entry.source))
+}
+
+fn list_line(entry: &HistoryEntry, now: u64) -> Line<'static> {
+    let kind = match entry.content {
+        ClipboardContent::Text(_) => "文本",
+        ClipboardContent::Image { .. } => "图片",
+    };
+    Line::from(vec![
+        Span::raw(if entry.pinned { "📌 " } else { "   " }),
+        Span::styled(
+            format!("{:>8}前 ", control::format_elapsed(now.saturating_sub(entry.timestamp))),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(format!("{} ", source_name(&entry.source)), Style::default().fg(Color::Cyan)),
+        Span::styled(format!("{} ", kind), Style::default().fg(Color::Yellow)),
+        Span::raw(entry.content.preview(LIST_PREVIEW_LENGTH).replace(['\n', '\r', '\t'], " ")),
+    ])
+}
+
+fn preview_text(entry: &HistoryEntry) -> String {
+    match &entry.content {
+        ClipboardContent::Text(text) => text.clone(),
+        ClipboardContent::Image { width, height, data } => format!(
+            "图片 {}x{}\nPNG，{:.1} KB\n来源: {}\n哈希: {}",
+            width,
+            height,
+            data.len() as f64 / 1024.0,
+            source_name(&entry.source),
+            &entry.content_hash[..12]
+        ),
+    }
+}
+
+/// 模糊匹配：查询中的字符按顺序出现在文字中即匹配，不区分大小写
+///
+/// 连续匹配和单词开头的匹配得分更高，字符之间间隔越远得分越低；不匹配时返回 None。
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for wanted in query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase) {
+        let index = position + text[position..].iter().position(|c| *c == wanted)?;
+        score += 10;
+        if previous.is_some_and(|previous| index == previous + 1) {
+            score += 15;
+        } else if previous.is_some() {
+            score -= (index - position).min(10) as i64;
+        }
+        if index == 0 || !text[index - 1].is_alphanumeric() {
+            score += 8;
+        }
+        previous = Some(index);
+        position = index + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("hlo", "hello").is_some());
+        assert!(fuzzy_score("HeLLo", "say hello").is_some());
+        assert!(fuzzy_score("olh", "hello").is_none());
+        assert!(fuzzy_score("剪板", "剪贴板内容").is_some());
+
+        let consecutive = fuzzy_score("abc", "abc def").unwrap();
+        let scattered = fuzzy_score("abc", "xaxbxc").unwrap();
+        assert!(consecutive > scattered);
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use std::io::{Cursor, Read};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+use wl_clipboard_rs::{copy, paste};
+
+use crate::clipboard::{ClipboardBackend, ACCESS_TIMEOUT};
+use crate::concealed;
+use crate::network::ClipboardContent;
+
+const PNG_MIME_TYPE: &str = "image/png";
+
+/// 等待剪贴板所有者写完内容的最长时间，所有者卡住时不会让读取一直阻塞
+const READ_TIMEOUT: Duration = ACCESS_TIMEOUT;
+
+/// 通过 wlr/ext data-control 协议访问的 Wayland 剪贴板
+///
+/// arboard 在 Wayland 上只有窗口获得焦点时才能读取剪贴板，
+/// data-control 协议不需要窗口，后台运行的同步服务也能读写剪贴板。
+pub struct WaylandClipboard;
+
+impl WaylandClipboard {
+    /// 连接 `WAYLAND_DISPLAY` 指定的合成器，合成器不支持 data-control 协议时返回错误
+    pub fn new() -> Result<Self> {
+        match paste::get_mime_types(paste::ClipboardType::Regular, paste::Seat::Unspecified) {
+            Ok(_) | Err(paste::Error::ClipboardEmpty | paste::Error::NoSeats) => Ok(Self),
+            Err(e) => Err(anyhow::anyhow!("无法使用 Wayland data-control 协议: {}", e)),
+        }
+    }
+
+    /// 当前内容提供的全部 MIME 类型，剪贴板为空时返回空列表
+    fn mime_types(&self, clipboard: paste::ClipboardType) -> Result<Vec<String>> {
+        match paste::get_mime_types_ordered(clipboard, paste::Seat::Unspecified) {
+            Ok(types) => Ok(types),
+            Err(paste::Error::ClipboardEmpty | paste::Error::NoSeats) => Ok(Vec::new()),
+            Err(e) => Err(anyhow::anyhow!("读取剪贴板类型失败: {}", e)),
+        }
+    }
+
+    fn read(&self, clipboard: paste::ClipboardType, mime_type: paste::MimeType) -> Result<Option<Vec<u8>>> {
+        match paste::get_contents(clipboard, paste::Seat::Unspecified, mime_type) {
+            Ok((mut pipe, _)) => {
+                let data = read_with_timeout(&mut pipe, READ_TIMEOUT)
+                    .map_err(|e| anyhow::anyhow!("读取剪贴板失败: {}", e))?;
+                Ok(Some(data))
+            }
+            Err(paste::Error::NoSeats | paste::Error::ClipboardEmpty | paste::Error::NoMimeType) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("读取剪贴板失败: {}", e)),
+        }
+    }
+
+    fn read_text(&self, clipboard: paste::ClipboardType) -> Result<String> {
+        let data = self.read(clipboard, paste::MimeType::Text)?.unwrap_or_default();
+        String::from_utf8(data).map_err(|_| anyhow::anyhow!("剪贴板中的文字不是 UTF-8 编码"))
+    }
+
+    /// 提供内容直到被其他程序替换，`foreground` 为 false 时在后台线程中提供并立即返回
+    fn write(
+        &self,
+        clipboard: copy::ClipboardType,
+        data: &[u8],
+        mime_type: copy::MimeType,
+        foreground: bool,
+    ) -> Result<()> {
+        let mut options = copy::Options::new();
+        options.clipboard(clipboard).foreground(foreground);
+        options
+            .copy(copy::Source::Bytes(data.into()), mime_type)
+            .map_err(|e| anyhow::anyhow!("写入剪贴板失败: {}", e))
+    }
+}
+
+impl ClipboardBackend for WaylandClipboard {
+    fn get_text(&self) -> Result<String> {
+        self.read_text(paste::ClipboardType::Regular)
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        self.write(copy::ClipboardType::Regular, text.as_bytes(), copy::MimeType::Text, false)
+    }
+
+    fn get_image(&self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        // 优先读取 PNG，其他格式的图片转换为 PNG
+        let mime_types = self.mime_types(paste::ClipboardType::Regular)?;
+        let Some(mime_type) = mime_types
+            .iter()
+            .find(|mime_type| *mime_type == PNG_MIME_TYPE)
+            .or_else(|| mime_types.iter().find(|mime_type| mime_type.starts_with("image/")))
+        else {
+            return Ok(None);
+        };
+        let Some(data) = self.read(paste::ClipboardType::Regular, paste::MimeType::Specific(mime_type))? else {
+            return Ok(None);
+        };
+
+        if mime_type == PNG_MIME_TYPE {
+            let (width, height) = image::io::Reader::new(Cursor::new(&data))
+                .with_guessed_format()
+                .map_err(|e| anyhow::anyhow!("无法识别图片格式: {}", e))?
+                .into_dimensions()
+                .map_err(|e| anyhow::anyhow!("读取图片尺寸失败: {}", e))?;
+            return Ok(Some((width, height, data)));
+        }
+        match ClipboardContent::from_input(data)? {
+            ClipboardContent::Image { width, height, data } => Ok(Some((width, height, data))),
+            ClipboardContent::Text(_) => Ok(None),
+        }
+    }
+
+    fn set_image(&self, _width: u32, _height: u32, png_data: &[u8]) -> Result<()> {
+        self.write(
+            copy::ClipboardType::Regular,
+            png_data,
+            copy::MimeType::Specific(PNG_MIME_TYPE.to_string()),
+            false,
+        )
+    }
+
+    fn clear(&self) -> Result<()> {
+        copy::clear(copy::ClipboardType::Regular, copy::Seat::All)
+            .map_err(|e| anyhow::anyhow!("清空剪贴板失败: {}", e))
+    }
+
+    fn is_concealed(&self) -> bool {
+        self.mime_types(paste::ClipboardType::Regular)
+            .is_ok_and(|types| types.iter().any(|name| concealed::is_concealed_type(name)))
+    }
+
+    fn supports_primary(&self) -> bool {
+        true
+    }
+
+    fn get_primary(&self) -> Result<String> {
+        self.read_text(paste::ClipboardType::Primary)
+    }
+
+    fn set_primary(&self, text: &str) -> Result<()> {
+        self.write(copy::ClipboardType::Primary, text.as_bytes(), copy::MimeType::Text, false)
+    }
+
+    fn clear_primary(&self) -> Result<()> {
+        copy::clear(copy::ClipboardType::Primary, copy::Seat::All)
+            .map_err(|e| anyhow::anyhow!("清空 PRIMARY 选区失败: {}", e))
+    }
+
+    fn set_and_wait(&self, content: &ClipboardContent) -> Result<()> {
+        match content {
+            ClipboardContent::Text(text) => {
+                self.write(copy::ClipboardType::Regular, text.as_bytes(), copy::MimeType::Text, true)
+            }
+            ClipboardContent::Image { data, .. } => self.write(
+                copy::ClipboardType::Regular,
+                data,
+                copy::MimeType::Specific(PNG_MIME_TYPE.to_string()),
+                true,
+            ),
+        }
+    }
+}
+
+/// 读取到对方关闭管道为止，超过 `timeout` 仍未读完时返回 TimedOut 错误
+fn read_with_timeout(reader: &mut (impl Read + AsRawFd), timeout: Duration) -> std::io::Result<Vec<u8>> {
+    let deadline = Instant::now() + timeout;
+    let mut data = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut fds = libc::pollfd {
+            fd: reader.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = libc::c_int::try_from(remaining.as_millis()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: fds 指向一个有效的 pollfd，数量为 1
+        let ready = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+        if ready < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        if ready == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "剪贴板所有者没有及时提供内容"));
+        }
+
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(data),
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_read_with_timeout() {
+        let (mut writer, mut reader) = UnixStream::pair().unwrap();
+        writer.write_all("剪贴板内容".as_bytes()).unwrap();
+        drop(writer);
+        assert_eq!(read_with_timeout(&mut reader, READ_TIMEOUT).unwrap(), "剪贴板内容".as_bytes());
+
+        // 对方一直不关闭管道
+        let (_writer, mut reader) = UnixStream::pair().unwrap();
+        let error = read_with_timeout(&mut reader, Duration::from_millis(50)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    /// 需要支持 data-control 协议的合成器，例如 `WLR_BACKENDS=headless sway`，
+    /// 未设置 `WAYLAND_DISPLAY` 时跳过
+    #[test]
+    fn test_wayland_round_trip() {
+        if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            eprintln!("未设置 WAYLAND_DISPLAY，跳过 Wayland 测试");
+            return;
+        }
+        let clipboard = WaylandClipboard::new().unwrap();
+        clipboard.set_text("来自 Wayland 的测试内容").unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "来自 Wayland 的测试内容");
+
+        clipboard.set_primary("选中的文字").unwrap();
+        assert_eq!(clipboard.get_primary().unwrap(), "选中的文字");
+
+        clipboard.clear().unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "");
+    }
+}